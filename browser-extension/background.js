@@ -16,8 +16,22 @@ function showNotification(title, message) {
   });
 }
 
-// Helper to handle native messaging
-function sendToNativeHost(message) {
+// Friendly messages for the error codes reported by the native host
+const ERROR_MESSAGES = {
+  AppNotFound: "The OmniTagger app could not be found. Reinstall the app or register the native host again from its settings.",
+  LaunchFailed: "The OmniTagger app could not be started.",
+  PayloadTooLarge: "The image is too large to send to OmniTagger.",
+  UnsupportedImage: "OmniTagger could not read this image.",
+};
+
+function describeError(response) {
+  const friendly = ERROR_MESSAGES[response.code];
+  return friendly ? `${friendly} (${response.message})` : "OmniTagger reported an error: " + response.message;
+}
+
+// Helper to handle native messaging. `fallbackUrl` is sent instead when the
+// host cannot use the inline image data.
+function sendToNativeHost(message, fallbackUrl) {
   console.log("Sending to native host:", message.url ? "URL" : "Data URI");
   chrome.runtime.sendNativeMessage(
     "com.omnitagger.host",
@@ -29,7 +43,12 @@ function sendToNativeHost(message) {
       } else {
         console.log("Response:", response);
         if (response && response.status === "error") {
-          showNotification("Error", "OmniTagger reported an error: " + response.message);
+          if (fallbackUrl && (response.code === "UnsupportedImage" || response.code === "PayloadTooLarge")) {
+            console.log("Host rejected the image data, falling back to sending original URL");
+            sendToNativeHost({ url: fallbackUrl });
+            return;
+          }
+          showNotification("Error", describeError(response));
         } else if (response && response.status === "ok") {
            showNotification("OmniTagger", response.message || "Processing started...");
         }
@@ -118,7 +137,7 @@ chrome.contextMenus.onClicked.addListener((info, tab) => {
                 }
             } else {
                 console.log("Received processed data URI from content script");
-                sendToNativeHost({ data: results[0].result });
+                sendToNativeHost({ data: results[0].result }, info.srcUrl.startsWith("blob:") ? undefined : info.srcUrl);
             }
         });
     } else {
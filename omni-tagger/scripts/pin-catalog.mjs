@@ -0,0 +1,96 @@
+// Downloads every file of the bundled model catalog and pins its SHA-256 (and the
+// model size) in catalog.json, so the app verifies downloads against known values
+// instead of trusting the hash published by the server it downloads from.
+//
+//   node scripts/pin-catalog.mjs          fill in missing values
+//   node scripts/pin-catalog.mjs --force  recompute every value
+//   node scripts/pin-catalog.mjs --check  compare pinned values with the files, write nothing
+import { createHash } from 'node:crypto';
+import { readFileSync, writeFileSync } from 'node:fs';
+import { resolve, dirname } from 'node:path';
+import { fileURLToPath } from 'node:url';
+
+const __dirname = dirname(fileURLToPath(import.meta.url));
+const catalogPath = resolve(
+  __dirname,
+  '..',
+  'src-tauri',
+  'crates',
+  'omni-tagger-core',
+  'src',
+  'catalog.json',
+);
+
+const check = process.argv.includes('--check');
+const force = process.argv.includes('--force');
+
+// Several models share one tags file; hash each URL once
+const cache = new Map();
+
+async function digest(url) {
+  if (!cache.has(url)) {
+    cache.set(url, (async () => {
+      console.log(`Downloading ${url}`);
+      const res = await fetch(url, { redirect: 'follow' });
+      if (!res.ok) {
+        throw new Error(`${url}: HTTP ${res.status}`);
+      }
+      const hash = createHash('sha256');
+      let size = 0;
+      for await (const chunk of res.body) {
+        hash.update(chunk);
+        size += chunk.length;
+      }
+      return { sha256: hash.digest('hex'), size };
+    })());
+  }
+  return cache.get(url);
+}
+
+const catalog = JSON.parse(readFileSync(catalogPath, 'utf8'));
+let mismatches = 0;
+
+for (const entry of catalog.models) {
+  const files = [
+    { url: entry.model_url, shaKey: 'sha256', sizeKey: 'size' },
+    { url: entry.tags_url, shaKey: 'tags_sha256', sizeKey: null },
+  ];
+  for (const { url, shaKey, sizeKey } of files) {
+    if (!url) continue;
+    if (!check && !force && entry[shaKey] && (!sizeKey || entry[sizeKey])) continue;
+
+    const { sha256, size } = await digest(url);
+    if (check) {
+      if (entry[shaKey] !== sha256 || (sizeKey && entry[sizeKey] !== size)) {
+        console.error(`MISMATCH ${entry.id} ${shaKey}: pinned ${entry[shaKey]}, got ${sha256} (${size} bytes)`);
+        mismatches += 1;
+      }
+      continue;
+    }
+    entry[shaKey] = sha256;
+    if (sizeKey) entry[sizeKey] = size;
+  }
+}
+
+if (check) {
+  if (mismatches > 0) process.exit(1);
+  console.log('All pinned checksums match.');
+  process.exit(0);
+}
+
+// Keep the layout of the hand-written file: one field per line, preprocessing inline
+const fields = ['id', 'name', 'model_url', 'tags_url', 'model_path', 'tags_path', 'sha256', 'tags_sha256', 'size'];
+const entries = catalog.models.map((entry) => {
+  const lines = fields
+    .filter((key) => entry[key] !== undefined && entry[key] !== null)
+    .map((key) => `      ${JSON.stringify(key)}: ${JSON.stringify(entry[key])}`);
+  if (entry.preprocessing) {
+    const pre = Object.entries(entry.preprocessing)
+      .map(([key, value]) => `${JSON.stringify(key)}: ${JSON.stringify(value)}`)
+      .join(', ');
+    lines.push(`      "preprocessing": { ${pre} }`);
+  }
+  return `    {\n${lines.join(',\n')}\n    }`;
+});
+writeFileSync(catalogPath, `{\n  "models": [\n${entries.join(',\n')}\n  ]\n}\n`);
+console.log(`Updated ${catalogPath}`);
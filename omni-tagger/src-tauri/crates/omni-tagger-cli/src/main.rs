@@ -0,0 +1,640 @@
+// Headless command line interface. Unlike the main executable this binary does
+// not touch Tauri, GTK or the webview, so it runs on servers and in containers.
+use anyhow::{anyhow, Context, Result};
+use clap::{Parser, Subcommand};
+use omni_tagger_core::bundle;
+use omni_tagger_core::catalog::Catalog;
+use omni_tagger_core::checksum::ChecksumStatus;
+use omni_tagger_core::{decode, download, inventory, postprocess_tags, Tagger};
+use omni_tagger_settings::overrides::ConfigOverrides;
+use omni_tagger_settings::profiles;
+use omni_tagger_settings::settings::{self, AppConfig, ConfigSection};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use tokio_util::sync::CancellationToken;
+
+/// Bundle identifier from `tauri.conf.json`; the desktop app stores its config
+/// and models in directories named after it.
+const APP_IDENTIFIER: &str = "com.omnitagger.app";
+
+#[derive(Parser)]
+#[command(name = "omni-tagger-cli", version, about = "Tag images with OmniTagger without the desktop app")]
+struct Cli {
+    /// Path to config.json (defaults to the desktop app's config file)
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+
+    /// Use the tagging settings of a saved profile for this run
+    #[arg(long, global = true, value_name = "NAME")]
+    profile: Option<String>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Tag one or more images ("-" reads image data from standard input)
+    Tag {
+        #[arg(required = true)]
+        inputs: Vec<String>,
+
+        #[command(flatten)]
+        overrides: ConfigOverrides,
+
+        /// Print one JSON object per image instead of comma separated tags
+        #[arg(long)]
+        json: bool,
+    },
+    /// List, download or verify model files
+    Models {
+        #[command(subcommand)]
+        command: ModelsCommand,
+    },
+    /// Inspect, export or import the configuration
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+    /// Manage named profiles of tagging settings
+    Profiles {
+        #[command(subcommand)]
+        command: ProfilesCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum ModelsCommand {
+    /// List model files in the models directory
+    List,
+    /// List the models available for download
+    Catalog,
+    /// Download a catalog model and its tags file (defaults to the configured model)
+    Download {
+        /// Catalog id, see `models catalog`
+        id: Option<String>,
+    },
+    /// Check model files against the SHA-256 checksums recorded when they were downloaded
+    Verify,
+    /// Install a local .onnx file (with --tags) or an offline bundle (.zip, .tar, .tar.gz)
+    Import {
+        path: PathBuf,
+        /// Tags file for a plain .onnx model
+        #[arg(long)]
+        tags: Option<PathBuf>,
+    },
+    /// Delete an installed model (path relative to the models directory or absolute)
+    Delete { path: PathBuf },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// Print the config file location and the effective configuration
+    Show,
+    /// Write the configuration, or only some sections of it, to a file for sharing
+    /// (without the API server, watch folders or proxy credentials)
+    Export {
+        /// Output file (standard output if omitted)
+        file: Option<PathBuf>,
+        /// Export only this section (repeatable)
+        #[arg(long = "section", value_enum, value_name = "SECTION")]
+        sections: Vec<ConfigSection>,
+    },
+    /// Apply settings written by `config export` to the config file
+    Import {
+        file: PathBuf,
+        /// Import only this section (repeatable); without it exclusions, formatting
+        /// and model are imported
+        #[arg(long = "section", value_enum, value_name = "SECTION")]
+        sections: Vec<ConfigSection>,
+        /// Merge nested settings and add to lists such as the exclusion list instead
+        /// of replacing them
+        #[arg(long)]
+        merge: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ProfilesCommand {
+    /// List the saved profiles
+    List,
+    /// Save the current tagging settings (threshold, formatting, model) as a profile
+    Save { name: String },
+    /// Delete a profile
+    Delete { name: String },
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(cli).await {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("Error: {:#}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(cli: Cli) -> Result<ExitCode> {
+    let config_path = match cli.config {
+        Some(path) => path,
+        None => default_config_path()?,
+    };
+    let mut config = load_config(&config_path)?;
+    let config_dir = config_path.parent().unwrap_or(Path::new("."));
+    let profiles_dir = profiles::profiles_dir(config_dir);
+    if let Some(name) = &cli.profile {
+        config = profiles::load(&profiles_dir, name)?.apply(&config);
+    }
+
+    match cli.command {
+        Command::Tag {
+            inputs,
+            overrides,
+            json,
+        } => tag(&overrides.apply(&config), config_dir, &inputs, json).await,
+        Command::Models { command } => {
+            match command {
+                ModelsCommand::List => list_models(&config)?,
+                ModelsCommand::Catalog => list_catalog(&config)?,
+                ModelsCommand::Download { id } => {
+                    let (model_path, tags_path) = match id {
+                        Some(id) => {
+                            let catalog = Catalog::load(&user_catalog_path()?)?;
+                            let entry = catalog.get(&id).ok_or_else(|| {
+                                anyhow!(
+                                    "Unknown model {:?}. Run `omni-tagger-cli models catalog` to list available models",
+                                    id
+                                )
+                            })?;
+                            (
+                                resolve_path(&entry.model_path)?,
+                                resolve_path(&entry.tags_path)?,
+                            )
+                        }
+                        None => (
+                            resolve_path(&config.model_path)?,
+                            resolve_path(&config.tags_path)?,
+                        ),
+                    };
+                    ensure_models(&config, &model_path, &tags_path, true).await?;
+                }
+                ModelsCommand::Verify => return verify_models(&config),
+                ModelsCommand::Import { path, tags } => {
+                    let dir = models_dir()?;
+                    let entry = match tags {
+                        Some(tags) => bundle::import_files(&dir, &path, &tags)?,
+                        None => bundle::import_bundle(&dir, &path)?,
+                    };
+                    Catalog::register(&user_catalog_path()?, entry.clone())?;
+                    println!("Imported {} to {}", entry.id, entry.model_path);
+                }
+                ModelsCommand::Delete { path } => {
+                    let dir = models_dir()?;
+                    let path = dir.join(path);
+                    if path == resolve_path(&config.model_path)? {
+                        return Err(anyhow!(
+                            "{:?} is the configured model; switch to another model first",
+                            path
+                        ));
+                    }
+                    let freed = inventory::delete_model(&dir, &path)?;
+                    println!(
+                        "Deleted {} ({:.1} MB freed)",
+                        path.display(),
+                        freed as f64 / (1024.0 * 1024.0)
+                    );
+                }
+            }
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Config { command } => {
+            match command {
+                ConfigCommand::Show => {
+                    println!("# {}", config_path.display());
+                    if let Some(name) = &cli.profile {
+                        println!("# with profile {:?}", name);
+                    }
+                    println!("{}", serde_json::to_string_pretty(&config)?);
+                }
+                ConfigCommand::Export { file, sections } => {
+                    let exported = settings::export_config(&config, &sections)?;
+                    let content = serde_json::to_string_pretty(&exported)?;
+                    match file {
+                        Some(file) => {
+                            std::fs::write(&file, content)
+                                .context(format!("Failed to write {:?}", file))?;
+                            eprintln!("Exported settings to {}", file.display());
+                        }
+                        None => println!("{}", content),
+                    }
+                }
+                ConfigCommand::Import {
+                    file,
+                    sections,
+                    merge,
+                } => import_config(&config_path, &file, &sections, merge)?,
+            }
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Profiles { command } => {
+            match command {
+                ProfilesCommand::List => {
+                    for name in profiles::list(&profiles_dir)? {
+                        if config.active_profile.as_deref() == Some(name.as_str()) {
+                            println!("{} (active)", name);
+                        } else {
+                            println!("{}", name);
+                        }
+                    }
+                }
+                ProfilesCommand::Save { name } => {
+                    profiles::save(&profiles_dir, &name, &profiles::Profile::from_config(&config))?;
+                    println!("Saved profile {:?}", name);
+                }
+                ProfilesCommand::Delete { name } => {
+                    profiles::delete(&profiles_dir, &name)?;
+                    println!("Deleted profile {:?}", name);
+                }
+            }
+            Ok(ExitCode::SUCCESS)
+        }
+    }
+}
+
+fn app_dir(base: Option<PathBuf>, kind: &str) -> Result<PathBuf> {
+    base.map(|dir| dir.join(APP_IDENTIFIER))
+        .ok_or_else(|| anyhow!("Could not determine the {} directory", kind))
+}
+
+fn default_config_path() -> Result<PathBuf> {
+    Ok(app_dir(dirs::config_dir(), "config")?.join("config.json"))
+}
+
+fn user_catalog_path() -> Result<PathBuf> {
+    Ok(app_dir(dirs::config_dir(), "config")?.join("models.json"))
+}
+
+fn models_dir() -> Result<PathBuf> {
+    Ok(app_dir(dirs::data_local_dir(), "local data")?.join("models"))
+}
+
+/// Resolves a model or tags path the same way the desktop app does: absolute
+/// paths are used as-is, relative ones live under the app's local data directory.
+fn resolve_path(path_str: &str) -> Result<PathBuf> {
+    let path = Path::new(path_str);
+    if path.is_absolute() {
+        Ok(path.to_path_buf())
+    } else {
+        Ok(app_dir(dirs::data_local_dir(), "local data")?.join(path))
+    }
+}
+
+fn load_config(path: &Path) -> Result<AppConfig> {
+    if !path.exists() {
+        return Ok(AppConfig::default());
+    }
+    let content =
+        std::fs::read_to_string(path).context(format!("Failed to read config at {:?}", path))?;
+    // Older configs are migrated in memory only; the desktop app rewrites the file
+    let (config, _) = settings::parse_config(&content)
+        .context(format!("Failed to parse config at {:?}", path))?;
+    Ok(config)
+}
+
+fn check_config(config: &AppConfig) -> Result<()> {
+    let errors = config.validate();
+    if !errors.is_empty() {
+        let errors: Vec<String> = errors.iter().map(ToString::to_string).collect();
+        return Err(anyhow!("Invalid settings: {}", errors.join("; ")));
+    }
+    Ok(())
+}
+
+/// Applies an exported config to the file at `config_path`. The file is read again
+/// so that a `--profile` given for this run is not written into it.
+fn import_config(
+    config_path: &Path,
+    file: &Path,
+    sections: &[ConfigSection],
+    merge: bool,
+) -> Result<()> {
+    let content =
+        std::fs::read_to_string(file).context(format!("Failed to read {:?}", file))?;
+    let config = settings::import_config(&load_config(config_path)?, &content, sections, merge)
+        .context(format!("Failed to import {:?}", file))?;
+    check_config(&config)?;
+
+    if let Some(parent) = config_path.parent() {
+        std::fs::create_dir_all(parent).context(format!("Failed to create {:?}", parent))?;
+    }
+    std::fs::write(config_path, serde_json::to_string_pretty(&config)?)
+        .context(format!("Failed to write {:?}", config_path))?;
+    println!("Imported settings from {} into {}", file.display(), config_path.display());
+    Ok(())
+}
+
+/// Downloads the model and tags file if they are missing (or always, if `force` is set).
+async fn ensure_models(
+    config: &AppConfig,
+    model_path: &Path,
+    tags_path: &Path,
+    force: bool,
+) -> Result<()> {
+    let catalog = Catalog::load(&user_catalog_path()?)?;
+    if force || !model_path.exists() {
+        let entry = catalog
+            .find_by_model_path(model_path)
+            .filter(|e| !e.model_url.is_empty());
+        let entry = entry.ok_or_else(|| {
+            anyhow!(
+                "Model file not found at {:?} and cannot be automatically downloaded",
+                model_path
+            )
+        })?;
+        download_with_status(
+            config,
+            &entry.model_url,
+            model_path,
+            entry.sha256.as_deref(),
+        )
+        .await?;
+    }
+    if !tags_path.exists() {
+        let entry = catalog
+            .find_by_tags_path(tags_path)
+            .filter(|e| !e.tags_url.is_empty());
+        let entry = entry.ok_or_else(|| {
+            anyhow!(
+                "Tags file not found at {:?} and cannot be automatically downloaded",
+                tags_path
+            )
+        })?;
+        download_with_status(
+            config,
+            &entry.tags_url,
+            tags_path,
+            entry.tags_sha256.as_deref(),
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+async fn download_with_status(
+    config: &AppConfig,
+    url: &str,
+    dest: &Path,
+    sha256: Option<&str>,
+) -> Result<()> {
+    eprintln!("Downloading {} to {:?}", url, dest);
+    let policy = download::RetryPolicy::default();
+    let cancel = CancellationToken::new();
+    let network = &config.network;
+    download::download_with_retry(url, dest, sha256, network, &policy, &cancel, |progress| {
+        let rate = progress.bytes_per_sec / 1_048_576.0;
+        if progress.is_indeterminate() {
+            let done = progress.downloaded as f64 / 1_048_576.0;
+            eprint!("\r{}: {:.1} MiB ({:.1} MiB/s)", progress.file, done, rate);
+        } else {
+            let eta = progress
+                .eta_secs
+                .map(|secs| format!(", {:.0}s left", secs))
+                .unwrap_or_default();
+            eprint!(
+                "\r{}: {:.1}% ({:.1} MiB/s{})   ",
+                progress.file, progress.percent, rate, eta
+            );
+        }
+        let _ = std::io::stderr().flush();
+    })
+    .await?;
+    eprintln!();
+    Ok(())
+}
+
+fn list_models(config: &AppConfig) -> Result<()> {
+    let dir = models_dir()?;
+    let active = resolve_path(&config.model_path)?;
+    println!("# {}", dir.display());
+
+    for path in inventory::find_models(&dir) {
+        let size = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+        let marker = if path == active { "*" } else { " " };
+        let name = path.strip_prefix(&dir).unwrap_or(&path);
+        let tags = inventory::tags_for_model(&path)
+            .and_then(|tags| inventory::count_tags(&tags).ok())
+            .map(|count| format!("{} tags", count))
+            .unwrap_or_else(|| "no tags file".to_string());
+        println!(
+            "{} {:<48} {:>8.1} MB  {}",
+            marker,
+            name.display(),
+            size as f64 / (1024.0 * 1024.0),
+            tags
+        );
+    }
+    println!(
+        "Total disk usage: {:.1} MB",
+        inventory::disk_usage(&dir) as f64 / (1024.0 * 1024.0)
+    );
+    Ok(())
+}
+
+fn list_catalog(config: &AppConfig) -> Result<()> {
+    let catalog = Catalog::load(&user_catalog_path()?)?;
+    for entry in &catalog.models {
+        let installed = resolve_path(&entry.model_path)?.exists();
+        let marker = if entry.model_path == config.model_path {
+            "*"
+        } else {
+            " "
+        };
+        println!(
+            "{} {:<32} {:<24} {}",
+            marker,
+            entry.id,
+            entry.name,
+            if installed { "installed" } else { "" }
+        );
+    }
+    Ok(())
+}
+
+fn verify_models(config: &AppConfig) -> Result<ExitCode> {
+    let catalog = Catalog::load(&user_catalog_path()?)?;
+    let mut paths = vec![
+        resolve_path(&config.model_path)?,
+        resolve_path(&config.tags_path)?,
+    ];
+    for model in inventory::find_models(&models_dir()?) {
+        let tags = inventory::tags_for_model(&model);
+        for path in std::iter::once(model).chain(tags) {
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+    }
+
+    let mut failed = false;
+    for path in paths {
+        match inventory::checksum_status(&path, &catalog)? {
+            ChecksumStatus::Ok { .. } => println!("OK          {}", path.display()),
+            ChecksumStatus::Unverified { sha256 } => {
+                println!("UNVERIFIED  {} (sha256 {})", path.display(), sha256)
+            }
+            ChecksumStatus::Mismatch { expected, actual } => {
+                failed = true;
+                println!(
+                    "MISMATCH    {} (expected {}, got {})",
+                    path.display(),
+                    expected,
+                    actual
+                );
+            }
+            ChecksumStatus::Missing => {
+                failed = true;
+                println!("MISSING     {}", path.display());
+            }
+        }
+    }
+    Ok(if failed {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    })
+}
+
+fn load_image(input: &str) -> Result<image::DynamicImage> {
+    if input == "-" {
+        let bytes = decode::read_input(std::io::stdin().lock())
+            .context("Failed to read image data from standard input")?;
+        decode::decode_image(&bytes).context("Failed to load image from standard input")
+    } else {
+        decode::open_image(Path::new(input)).context(format!("Failed to open image at {:?}", input))
+    }
+}
+
+async fn tag(
+    config: &AppConfig,
+    config_dir: &Path,
+    inputs: &[String],
+    json: bool,
+) -> Result<ExitCode> {
+    check_config(config)?;
+    let exclusions = config.exclusions(config_dir)?;
+
+    let model_path = resolve_path(&config.model_path)?;
+    let tags_path = resolve_path(&config.tags_path)?;
+    ensure_models(config, &model_path, &tags_path, false).await?;
+
+    let mut tagger = Tagger::new(
+        model_path.to_str().unwrap_or(&config.model_path),
+        tags_path.to_str().unwrap_or(&config.tags_path),
+        config.preprocessing.clone(),
+    )?;
+
+    let mut failed = false;
+    for input in inputs {
+        let result = load_image(input).and_then(|img| Ok(tagger.infer(&img, config.threshold)?));
+        let tags = match result {
+            Ok(results) => {
+                postprocess_tags(results, &exclusions, config.use_underscore)
+            }
+            Err(e) => {
+                eprintln!("{}: {:#}", input, e);
+                failed = true;
+                continue;
+            }
+        };
+
+        if json {
+            let record = serde_json::json!({
+                "file": input,
+                "tags": tags
+                    .iter()
+                    .map(|(tag, score)| serde_json::json!({ "tag": tag, "score": score }))
+                    .collect::<Vec<_>>(),
+            });
+            println!("{}", record);
+        } else {
+            let text = tags
+                .iter()
+                .map(|(t, _)| t.as_str())
+                .collect::<Vec<_>>()
+                .join(", ");
+            if inputs.len() > 1 {
+                println!("{}: {}", input, text);
+            } else {
+                println!("{}", text);
+            }
+        }
+    }
+
+    Ok(if failed {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn test_cli_definition() {
+        Cli::command().debug_assert();
+
+        let cli = Cli::try_parse_from(["omni-tagger-cli", "tag", "-", "--json"]).unwrap();
+        assert!(matches!(cli.command, Command::Tag { json: true, .. }));
+        let cli = Cli::try_parse_from(["omni-tagger-cli", "tag", "a.png", "--profile", "prompt copy"])
+            .unwrap();
+        assert_eq!(cli.profile.as_deref(), Some("prompt copy"));
+        let cli = Cli::try_parse_from([
+            "omni-tagger-cli",
+            "tag",
+            "a.png",
+            "--threshold",
+            "0.5",
+            "--no-underscore",
+        ])
+        .unwrap();
+        match cli.command {
+            Command::Tag { overrides, .. } => {
+                assert_eq!(overrides.threshold, Some(0.5));
+                assert!(overrides.no_underscore);
+            }
+            _ => panic!("expected tag"),
+        }
+        assert!(Cli::try_parse_from(["omni-tagger-cli", "tag"]).is_err());
+
+        let cli = Cli::try_parse_from([
+            "omni-tagger-cli",
+            "config",
+            "import",
+            "team.json",
+            "--section",
+            "exclusions",
+            "--section",
+            "formatting",
+            "--merge",
+        ])
+        .unwrap();
+        assert!(matches!(
+            cli.command,
+            Command::Config {
+                command: ConfigCommand::Import { ref sections, merge: true, .. }
+            } if sections == &[ConfigSection::Exclusions, ConfigSection::Formatting]
+        ));
+    }
+
+    #[test]
+    fn test_resolve_path_absolute() {
+        let abs = std::env::temp_dir().join("model.onnx");
+        assert_eq!(resolve_path(abs.to_str().unwrap()).unwrap(), abs);
+    }
+}
@@ -0,0 +1,420 @@
+use crate::catalog::ModelEntry;
+use crate::checksum::{normalize_sha256, record_checksum, recorded_checksum, sha256_file};
+use crate::error::{Error, Result};
+use crate::inventory::count_tags;
+use crate::tagger::{model_input_shape, PreprocessConfig};
+use serde::Deserialize;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+
+/// Name of the manifest at the root of a bundle.
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// Manifests larger than this are not read into memory.
+const MAX_MANIFEST_SIZE: u64 = 64 * 1024;
+
+/// Describes the model inside an offline bundle, e.g.
+///
+/// ```json
+/// { "id": "wd-vit-tagger-v3", "name": "WD ViT v3",
+///   "sha256": "…", "preprocessing": { "input_size": 448, "format": "bgr", "normalize": false } }
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+pub struct BundleManifest {
+    pub id: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default = "default_model_file")]
+    pub model: String,
+    #[serde(default = "default_tags_file")]
+    pub tags: String,
+    /// SHA-256 of the model file; the import fails if it does not match.
+    #[serde(default)]
+    pub sha256: Option<String>,
+    #[serde(default)]
+    pub tags_sha256: Option<String>,
+    #[serde(default)]
+    pub preprocessing: PreprocessConfig,
+}
+
+fn default_model_file() -> String {
+    "model.onnx".to_string()
+}
+
+fn default_tags_file() -> String {
+    "selected_tags.csv".to_string()
+}
+
+/// Installs a local `.onnx` model and its tags file into `models_dir/<model name>/`.
+///
+/// A checksum recorded next to the source model (`<model>.sha256`) is verified.
+pub fn import_files(models_dir: &Path, model: &Path, tags: &Path) -> Result<ModelEntry> {
+    let id = model
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .ok_or_else(|| Error::InvalidBundle(format!("Invalid model file name {:?}", model)))?
+        .to_string();
+    let manifest = BundleManifest {
+        name: None,
+        model: default_model_file(),
+        tags: default_tags_file(),
+        sha256: recorded_checksum(model),
+        tags_sha256: recorded_checksum(tags),
+        preprocessing: PreprocessConfig::default(),
+        id,
+    };
+    install(models_dir, &manifest, model, tags)
+}
+
+/// Installs the model from a bundle: a `.zip`, `.tar`, `.tar.gz`/`.tgz` archive or a
+/// directory containing `manifest.json`, the model and its tags file.
+///
+/// Only files at the root of an archive are considered, so entries cannot be written
+/// outside the models directory.
+pub fn import_bundle(models_dir: &Path, bundle: &Path) -> Result<ModelEntry> {
+    if bundle.is_dir() {
+        return import_dir(models_dir, bundle);
+    }
+
+    std::fs::create_dir_all(models_dir)
+        .map_err(|e| Error::io(format!("Failed to create {:?}", models_dir), e))?;
+    // Extracted next to the installed models, on the disk they have to fit on
+    let staging = models_dir.join(format!(".import-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&staging);
+    std::fs::create_dir_all(&staging)
+        .map_err(|e| Error::io(format!("Failed to create {:?}", staging), e))?;
+
+    let result = extract(bundle, &staging).and_then(|()| import_dir(models_dir, &staging));
+    let _ = std::fs::remove_dir_all(&staging);
+    result
+}
+
+fn import_dir(models_dir: &Path, dir: &Path) -> Result<ModelEntry> {
+    let manifest_path = dir.join(MANIFEST_FILE);
+    let file = File::open(&manifest_path)
+        .map_err(|e| Error::InvalidBundle(format!("Missing {} in bundle: {}", MANIFEST_FILE, e)))?;
+    let mut content = String::new();
+    file.take(MAX_MANIFEST_SIZE)
+        .read_to_string(&mut content)
+        .map_err(|e| Error::io(format!("Failed to read {:?}", manifest_path), e))?;
+    let manifest: BundleManifest = serde_json::from_str(&content)
+        .map_err(|e| Error::InvalidBundle(format!("Invalid {}: {}", MANIFEST_FILE, e)))?;
+
+    let model = dir.join(file_name(&manifest.model)?);
+    let tags = dir.join(file_name(&manifest.tags)?);
+    install(models_dir, &manifest, &model, &tags)
+}
+
+/// Verifies the files and copies them to `models_dir/<id>/`.
+fn install(
+    models_dir: &Path,
+    manifest: &BundleManifest,
+    model: &Path,
+    tags: &Path,
+) -> Result<ModelEntry> {
+    let id = file_name(&manifest.id)?;
+    verify(model, manifest.sha256.as_deref())?;
+    verify(tags, manifest.tags_sha256.as_deref())?;
+    if count_tags(tags)? == 0 {
+        return Err(Error::TagsFile {
+            path: tags.to_path_buf(),
+            message: "No tags found".to_string(),
+        });
+    }
+    // Fails with ModelLoad for anything that is not an ONNX model
+    model_input_shape(model)?;
+
+    let folder = models_dir.join(id);
+    if folder.exists() {
+        return Err(Error::InvalidBundle(format!(
+            "A model named {:?} is already installed",
+            id
+        )));
+    }
+    std::fs::create_dir_all(&folder)
+        .map_err(|e| Error::io(format!("Failed to create {:?}", folder), e))?;
+
+    let installed = (|| {
+        let model_dest = folder.join(default_model_file());
+        let tags_dest = folder.join(default_tags_file());
+        let sha256 = copy_verified(model, &model_dest)?;
+        let tags_sha256 = copy_verified(tags, &tags_dest)?;
+        let size = std::fs::metadata(&model_dest)
+            .map_err(|e| Error::io(format!("Failed to read {:?}", model_dest), e))?
+            .len();
+        Ok(ModelEntry {
+            id: id.to_string(),
+            name: manifest.name.clone().unwrap_or_else(|| id.to_string()),
+            model_url: String::new(),
+            tags_url: String::new(),
+            model_path: format!("models/{}/{}", id, default_model_file()),
+            tags_path: format!("models/{}/{}", id, default_tags_file()),
+            sha256: Some(sha256),
+            tags_sha256: Some(tags_sha256),
+            size: Some(size),
+            preprocessing: manifest.preprocessing.clone(),
+        })
+    })();
+    if installed.is_err() {
+        let _ = std::fs::remove_dir_all(&folder);
+    }
+    installed
+}
+
+/// Accepts a plain file name, rejecting anything that could point elsewhere.
+fn file_name(name: &str) -> Result<&str> {
+    let mut components = Path::new(name).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(_)), None) if !name.starts_with('.') => Ok(name),
+        _ => Err(Error::InvalidBundle(format!(
+            "Invalid file name {:?}",
+            name
+        ))),
+    }
+}
+
+fn verify(path: &Path, expected: Option<&str>) -> Result<()> {
+    if !path.is_file() {
+        return Err(Error::InvalidBundle(format!("Missing file {:?}", path)));
+    }
+    let Some(expected) = expected else {
+        return Ok(());
+    };
+    let expected = normalize_sha256(expected)
+        .ok_or_else(|| Error::InvalidBundle(format!("Invalid SHA-256 {:?}", expected)))?;
+    let actual = sha256_file(path)?;
+    if actual != expected {
+        return Err(Error::ChecksumMismatch {
+            path: path.to_path_buf(),
+            expected,
+            actual,
+        });
+    }
+    Ok(())
+}
+
+/// Copies `src` to `dest` and records its checksum, returning it.
+fn copy_verified(src: &Path, dest: &Path) -> Result<String> {
+    std::fs::copy(src, dest)
+        .map_err(|e| Error::io(format!("Failed to copy {:?} to {:?}", src, dest), e))?;
+    let sha256 = sha256_file(dest)?;
+    record_checksum(dest, &sha256)?;
+    Ok(sha256)
+}
+
+enum ArchiveKind {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+fn archive_kind(path: &Path) -> Option<ArchiveKind> {
+    let name = path.file_name()?.to_str()?.to_ascii_lowercase();
+    if name.ends_with(".zip") {
+        Some(ArchiveKind::Zip)
+    } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        Some(ArchiveKind::TarGz)
+    } else if name.ends_with(".tar") {
+        Some(ArchiveKind::Tar)
+    } else {
+        None
+    }
+}
+
+/// Root-level entry name of an archive, or `None` for nested or unsafe paths.
+fn root_entry(path: &Path) -> Option<PathBuf> {
+    let mut components = path
+        .components()
+        .filter(|c| !matches!(c, Component::CurDir));
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(name)), None) => Some(PathBuf::from(name)),
+        _ => None,
+    }
+}
+
+fn extract(bundle: &Path, staging: &Path) -> Result<()> {
+    let open =
+        || File::open(bundle).map_err(|e| Error::io(format!("Failed to open {:?}", bundle), e));
+    let bad_archive = |e: &dyn std::fmt::Display| {
+        Error::InvalidBundle(format!("Failed to read {:?}: {}", bundle, e))
+    };
+
+    match archive_kind(bundle) {
+        Some(ArchiveKind::Zip) => {
+            let mut archive = zip::ZipArchive::new(open()?).map_err(|e| bad_archive(&e))?;
+            for i in 0..archive.len() {
+                let mut entry = archive.by_index(i).map_err(|e| bad_archive(&e))?;
+                let Some(name) = entry.enclosed_name().as_deref().and_then(root_entry) else {
+                    continue;
+                };
+                if entry.is_file() {
+                    write_entry(&mut entry, &staging.join(name))?;
+                }
+            }
+        }
+        Some(kind) => {
+            let reader: Box<dyn Read> = match kind {
+                ArchiveKind::TarGz => Box::new(flate2::read::GzDecoder::new(open()?)),
+                _ => Box::new(open()?),
+            };
+            let mut archive = tar::Archive::new(reader);
+            for entry in archive.entries().map_err(|e| bad_archive(&e))? {
+                let mut entry = entry.map_err(|e| bad_archive(&e))?;
+                if !entry.header().entry_type().is_file() {
+                    continue;
+                }
+                let Some(name) = entry.path().ok().as_deref().and_then(root_entry) else {
+                    continue;
+                };
+                write_entry(&mut entry, &staging.join(name))?;
+            }
+        }
+        None => {
+            return Err(Error::InvalidBundle(format!(
+                "Unsupported bundle {:?}; expected a .zip, .tar, .tar.gz or .tgz file",
+                bundle
+            )))
+        }
+    }
+    Ok(())
+}
+
+fn write_entry(entry: &mut impl Read, dest: &Path) -> Result<()> {
+    let mut file =
+        File::create(dest).map_err(|e| Error::io(format!("Failed to create {:?}", dest), e))?;
+    std::io::copy(entry, &mut file)
+        .map_err(|e| Error::io(format!("Failed to extract {:?}", dest), e))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    const TAGS: &str = "tag_id,name\n0,general\n";
+
+    #[test]
+    fn test_root_entry() {
+        assert_eq!(
+            root_entry(Path::new("model.onnx")),
+            Some(PathBuf::from("model.onnx"))
+        );
+        assert_eq!(
+            root_entry(Path::new("./manifest.json")),
+            Some(PathBuf::from("manifest.json"))
+        );
+        assert_eq!(root_entry(Path::new("nested/model.onnx")), None);
+        assert_eq!(root_entry(Path::new("../model.onnx")), None);
+        assert_eq!(root_entry(Path::new("/model.onnx")), None);
+    }
+
+    #[test]
+    fn test_file_name() {
+        assert!(file_name("wd-vit-tagger-v3").is_ok());
+        for name in ["", ".", "..", "../x", "a/b", "/abs", ".hidden"] {
+            assert!(file_name(name).is_err(), "{:?} should be rejected", name);
+        }
+    }
+
+    #[test]
+    fn test_rejects_invalid_bundles() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let dir = std::env::temp_dir().join("omni_tagger_test_bundle_invalid");
+        let _ = std::fs::remove_dir_all(&dir);
+        let models_dir = dir.join("models");
+        let bundle = dir.join("bundle");
+        std::fs::create_dir_all(&bundle)?;
+
+        // No manifest
+        assert!(matches!(
+            import_bundle(&models_dir, &bundle),
+            Err(Error::InvalidBundle(_))
+        ));
+
+        // Checksum in the manifest does not match
+        let model = b"not really onnx";
+        std::fs::write(bundle.join("model.onnx"), model)?;
+        std::fs::write(bundle.join("selected_tags.csv"), TAGS)?;
+        std::fs::write(
+            bundle.join(MANIFEST_FILE),
+            format!(r#"{{"id": "test", "sha256": "{}"}}"#, "0".repeat(64)),
+        )?;
+        assert!(matches!(
+            import_bundle(&models_dir, &bundle),
+            Err(Error::ChecksumMismatch { .. })
+        ));
+        assert!(!models_dir.join("test").exists());
+
+        // Manifests cannot point outside the bundle
+        std::fs::write(
+            bundle.join(MANIFEST_FILE),
+            r#"{"id": "test", "model": "../model.onnx"}"#,
+        )?;
+        assert!(matches!(
+            import_bundle(&models_dir, &bundle),
+            Err(Error::InvalidBundle(_))
+        ));
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_archives() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let dir = std::env::temp_dir().join("omni_tagger_test_bundle_extract");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir)?;
+
+        let zip_path = dir.join("bundle.zip");
+        let mut zip = zip::ZipWriter::new(File::create(&zip_path)?);
+        let options = zip::write::SimpleFileOptions::default();
+        for (name, data) in [
+            ("manifest.json", r#"{"id": "test"}"#),
+            ("selected_tags.csv", TAGS),
+            ("../escape.csv", TAGS),
+            ("nested/other.csv", TAGS),
+        ] {
+            zip.start_file(name, options)?;
+            zip.write_all(data.as_bytes())?;
+        }
+        zip.finish()?;
+
+        let tar_path = dir.join("bundle.tar.gz");
+        let encoder =
+            flate2::write::GzEncoder::new(File::create(&tar_path)?, flate2::Compression::default());
+        let mut tar = tar::Builder::new(encoder);
+        for (name, data) in [
+            ("manifest.json", r#"{"id": "test"}"#),
+            ("selected_tags.csv", TAGS),
+        ] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            tar.append_data(&mut header, name, data.as_bytes())?;
+        }
+        tar.into_inner()?.finish()?;
+
+        for bundle in [zip_path, tar_path] {
+            let staging = dir.join("staging");
+            let _ = std::fs::remove_dir_all(&staging);
+            std::fs::create_dir_all(&staging)?;
+            extract(&bundle, &staging)?;
+
+            let mut names: Vec<_> = std::fs::read_dir(&staging)?
+                .flatten()
+                .map(|e| e.file_name().to_string_lossy().into_owned())
+                .collect();
+            names.sort();
+            assert_eq!(names, vec!["manifest.json", "selected_tags.csv"]);
+            assert!(!dir.join("escape.csv").exists());
+        }
+
+        assert!(matches!(
+            extract(&dir.join("bundle.rar"), &dir),
+            Err(Error::InvalidBundle(_))
+        ));
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,303 @@
+use crate::error::{Error, Result};
+use crate::tagger::PreprocessConfig;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Catalog shipped with the application. Users can add or override entries with
+/// their own file, see [`Catalog::load`].
+const BUILTIN_CATALOG: &str = include_str!("catalog.json");
+
+/// A downloadable model together with the settings it needs.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelEntry {
+    pub id: String,
+    pub name: String,
+    /// Empty for imported models, which cannot be downloaded again.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub model_url: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub tags_url: String,
+    /// Where the model is stored, relative to the app's local data directory
+    /// unless absolute.
+    pub model_path: String,
+    pub tags_path: String,
+    /// SHA-256 of the model file. Downloads and `verify_models` compare against it;
+    /// only when it is absent is the hash published by the server used.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// SHA-256 of the tags file, used the same way as `sha256`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tags_sha256: Option<String>,
+    /// Size of the model file in bytes, shown before downloading.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    #[serde(default)]
+    pub preprocessing: PreprocessConfig,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Catalog {
+    pub models: Vec<ModelEntry>,
+}
+
+impl Catalog {
+    pub fn builtin() -> Self {
+        Self::from_json(BUILTIN_CATALOG).expect("bundled catalog.json is valid")
+    }
+
+    pub fn from_json(json: &str) -> Result<Self> {
+        serde_json::from_str(json).map_err(|e| Error::Catalog(e.to_string()))
+    }
+
+    /// The built-in catalog extended with the entries of `user_catalog`, if that file
+    /// exists. User entries replace built-in ones with the same `id`.
+    pub fn load(user_catalog: &Path) -> Result<Self> {
+        let mut catalog = Self::builtin();
+        if user_catalog.exists() {
+            let content = std::fs::read_to_string(user_catalog)
+                .map_err(|e| Error::io(format!("Failed to read {:?}", user_catalog), e))?;
+            let user = Self::from_json(&content)
+                .map_err(|e| Error::Catalog(format!("{:?}: {}", user_catalog, e)))?;
+            catalog.merge(user);
+        }
+        Ok(catalog)
+    }
+
+    /// Adds `entry` to the user catalog at `user_catalog`, replacing an entry with
+    /// the same id, so it is offered like a built-in model from then on.
+    pub fn register(user_catalog: &Path, entry: ModelEntry) -> Result<()> {
+        let mut user = if user_catalog.exists() {
+            let content = std::fs::read_to_string(user_catalog)
+                .map_err(|e| Error::io(format!("Failed to read {:?}", user_catalog), e))?;
+            Self::from_json(&content)?
+        } else {
+            Self::default()
+        };
+        user.merge(Catalog {
+            models: vec![entry],
+        });
+
+        if let Some(parent) = user_catalog.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| Error::io(format!("Failed to create {:?}", parent), e))?;
+        }
+        let content =
+            serde_json::to_string_pretty(&user).map_err(|e| Error::Catalog(e.to_string()))?;
+        std::fs::write(user_catalog, content)
+            .map_err(|e| Error::io(format!("Failed to write {:?}", user_catalog), e))
+    }
+
+    pub fn merge(&mut self, other: Catalog) {
+        for entry in other.models {
+            match self.models.iter_mut().find(|m| m.id == entry.id) {
+                Some(existing) => *existing = entry,
+                None => self.models.push(entry),
+            }
+        }
+    }
+
+    pub fn get(&self, id: &str) -> Option<&ModelEntry> {
+        self.models.iter().find(|m| m.id == id)
+    }
+
+    /// Finds the entry whose model is stored at `path`, which may be the configured
+    /// relative path or the resolved absolute one.
+    pub fn find_by_model_path(&self, path: &Path) -> Option<&ModelEntry> {
+        self.models
+            .iter()
+            .find(|m| path_matches(path, &m.model_path))
+    }
+
+    /// Finds an entry that stores its tags at `path`, to know where to download them from.
+    pub fn find_by_tags_path(&self, path: &Path) -> Option<&ModelEntry> {
+        self.models
+            .iter()
+            .find(|m| path_matches(path, &m.tags_path))
+    }
+
+    /// The SHA-256 the catalog pins for the model or tags file stored at `path`.
+    /// Several entries may share a tags file; any of them can pin it.
+    pub fn pinned_checksum(&self, path: &Path) -> Option<&str> {
+        self.models.iter().find_map(|m| {
+            if path_matches(path, &m.model_path) {
+                m.sha256.as_deref()
+            } else if path_matches(path, &m.tags_path) {
+                m.tags_sha256.as_deref()
+            } else {
+                None
+            }
+        })
+    }
+}
+
+/// Compares whole trailing components, so `models/owner--repo/model.onnx` (hub
+/// downloads, imports) never matches the built-in `models/model.onnx`. Matching by
+/// file name alone would download the built-in model over a missing file of the
+/// same name.
+fn path_matches(path: &Path, entry_path: &str) -> bool {
+    path.ends_with(entry_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_catalog() {
+        let catalog = Catalog::builtin();
+        let entry = catalog
+            .get("wd-v1-4-swinv2-tagger-v2")
+            .expect("default model is in the catalog");
+        assert_eq!(entry.model_path, "models/model.onnx");
+        assert_eq!(entry.preprocessing, PreprocessConfig::default());
+
+        let mut ids: Vec<_> = catalog.models.iter().map(|m| &m.id).collect();
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), catalog.models.len());
+
+        for entry in &catalog.models {
+            // scripts/pin-catalog.mjs fills in all three together
+            assert_eq!(entry.sha256.is_some(), entry.size.is_some(), "{}", entry.id);
+            for pinned in [&entry.sha256, &entry.tags_sha256].into_iter().flatten() {
+                assert!(
+                    crate::checksum::normalize_sha256(pinned).as_deref() == Some(pinned.as_str()),
+                    "{}: {:?} is not a lowercase SHA-256",
+                    entry.id,
+                    pinned
+                );
+            }
+        }
+    }
+
+    /// Every built-in entry must carry pinned values, or downloads fall back to the
+    /// hash published by the server. Fill them in with `npm run pin-catalog`, which
+    /// needs network access, then drop the `ignore`.
+    #[test]
+    #[ignore = "catalog.json has not been pinned yet; run `npm run pin-catalog`"]
+    fn test_builtin_catalog_is_pinned() {
+        for entry in &Catalog::builtin().models {
+            assert!(entry.sha256.is_some(), "{} has no sha256", entry.id);
+            assert!(entry.tags_sha256.is_some(), "{} has no tags_sha256", entry.id);
+            assert!(entry.size.is_some(), "{} has no size", entry.id);
+        }
+    }
+
+    #[test]
+    fn test_pinned_checksum() {
+        let model_sha = "a".repeat(64);
+        let tags_sha = "b".repeat(64);
+        let catalog = Catalog {
+            models: vec![
+                ModelEntry {
+                    id: "unpinned".to_string(),
+                    name: "Unpinned".to_string(),
+                    model_url: String::new(),
+                    tags_url: String::new(),
+                    model_path: "models/other.onnx".to_string(),
+                    tags_path: "models/tags.csv".to_string(),
+                    sha256: None,
+                    tags_sha256: None,
+                    size: None,
+                    preprocessing: PreprocessConfig::default(),
+                },
+                ModelEntry {
+                    id: "pinned".to_string(),
+                    name: "Pinned".to_string(),
+                    model_url: String::new(),
+                    tags_url: String::new(),
+                    model_path: "models/model.onnx".to_string(),
+                    tags_path: "models/tags.csv".to_string(),
+                    sha256: Some(model_sha.clone()),
+                    tags_sha256: Some(tags_sha.clone()),
+                    size: None,
+                    preprocessing: PreprocessConfig::default(),
+                },
+            ],
+        };
+        let pinned = |p: &str| catalog.pinned_checksum(Path::new(p));
+        assert_eq!(pinned("models/model.onnx"), Some(model_sha.as_str()));
+        assert_eq!(pinned("/data/app/models/tags.csv"), Some(tags_sha.as_str()));
+        assert_eq!(pinned("models/other.onnx"), None);
+        assert_eq!(pinned("models/custom.onnx"), None);
+        // A hub model with the same file name is not the pinned one
+        assert_eq!(pinned("/data/models/owner--repo/model.onnx"), None);
+    }
+
+    #[test]
+    fn test_find_by_path() {
+        let catalog = Catalog::builtin();
+        let find = |p: &str| {
+            catalog
+                .find_by_model_path(Path::new(p))
+                .map(|m| m.id.as_str())
+        };
+        assert_eq!(find("models/model.onnx"), Some("wd-v1-4-swinv2-tagger-v2"));
+        assert_eq!(
+            find("/home/user/.local/share/com.omnitagger.app/models/convnextv2.onnx"),
+            Some("wd-v1-4-convnextv2-tagger-v2")
+        );
+        assert_eq!(find("custom_model.onnx"), None);
+        assert_eq!(find("/abs/path/to/convnext.onnx"), None);
+        assert_eq!(find("models/owner--repo/model.onnx"), None);
+        assert_eq!(
+            find("/home/user/.local/share/com.omnitagger.app/models/owner--repo/model.onnx"),
+            None
+        );
+        assert_eq!(find("some/other/file.txt"), None);
+
+        let tags = catalog.find_by_tags_path(Path::new("models/wd-vit-tagger-v3.csv"));
+        assert_eq!(tags.map(|m| m.id.as_str()), Some("wd-vit-tagger-v3"));
+    }
+
+    #[test]
+    fn test_load_merges_user_catalog() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let path = std::env::temp_dir().join("omni_tagger_test_catalog.json");
+        std::fs::write(
+            &path,
+            r#"{"models": [
+                {"id": "wd-vit-tagger-v3", "name": "Mirrored ViT",
+                 "model_url": "https://mirror.example/vit.onnx",
+                 "tags_url": "https://mirror.example/vit.csv",
+                 "model_path": "models/vit.onnx", "tags_path": "models/vit.csv"},
+                {"id": "custom", "name": "Custom",
+                 "model_url": "https://mirror.example/custom.onnx",
+                 "tags_url": "https://mirror.example/custom.csv",
+                 "model_path": "models/custom.onnx", "tags_path": "models/custom.csv",
+                 "size": 1024,
+                 "preprocessing": {"input_size": 384, "format": "rgb", "normalize": true}}
+            ]}"#,
+        )?;
+        let catalog = Catalog::load(&path)?;
+        std::fs::remove_file(&path)?;
+
+        assert_eq!(catalog.models.len(), Catalog::builtin().models.len() + 1);
+        let vit = catalog.get("wd-vit-tagger-v3").ok_or("missing vit")?;
+        assert_eq!(vit.model_url, "https://mirror.example/vit.onnx");
+        let custom = catalog.get("custom").ok_or("missing custom")?;
+        assert_eq!(custom.size, Some(1024));
+        assert_eq!(custom.preprocessing.input_size, 384);
+        assert_eq!(custom.preprocessing.format, crate::PixelFormat::Rgb);
+
+        // A missing user catalog is not an error
+        assert_eq!(Catalog::load(&path)?, Catalog::builtin());
+        Ok(())
+    }
+
+    #[test]
+    fn test_register() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let path = std::env::temp_dir().join("omni_tagger_test_register/models.json");
+        let _ = std::fs::remove_file(&path);
+
+        let mut entry = crate::hub::model_entry(crate::hub::DEFAULT_BASE_URL, "a/b", "main")?;
+        Catalog::register(&path, entry.clone())?;
+        entry.name = "Renamed".to_string();
+        Catalog::register(&path, entry)?;
+
+        let catalog = Catalog::load(&path)?;
+        std::fs::remove_file(&path)?;
+        assert_eq!(catalog.models.len(), Catalog::builtin().models.len() + 1);
+        assert_eq!(catalog.get("a/b").map(|m| m.name.as_str()), Some("Renamed"));
+        Ok(())
+    }
+}
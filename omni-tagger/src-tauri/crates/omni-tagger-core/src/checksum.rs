@@ -0,0 +1,170 @@
+use crate::error::{Error, Result};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Result of checking a file against its recorded SHA-256 checksum.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum ChecksumStatus {
+    Ok {
+        sha256: String,
+    },
+    Mismatch {
+        expected: String,
+        actual: String,
+    },
+    /// No checksum is pinned or recorded for the file, e.g. because it was copied in
+    /// by hand.
+    Unverified {
+        sha256: String,
+    },
+    Missing,
+}
+
+/// Lowercase hex encoding of a finished digest.
+pub(crate) fn to_hex(digest: &[u8]) -> String {
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn sha256_file(path: &Path) -> Result<String> {
+    let mut file = std::fs::File::open(path)
+        .map_err(|e| Error::io(format!("Failed to open {:?}", path), e))?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        let read = file
+            .read(&mut buffer)
+            .map_err(|e| Error::io(format!("Failed to read {:?}", path), e))?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(to_hex(&hasher.finalize()))
+}
+
+/// Path of the `sha256sum`-style sidecar file that records the checksum of `path`.
+pub fn checksum_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".sha256");
+    PathBuf::from(name)
+}
+
+/// Reads the checksum recorded next to `path`, if any.
+pub fn recorded_checksum(path: &Path) -> Option<String> {
+    let content = std::fs::read_to_string(checksum_path(path)).ok()?;
+    normalize_sha256(content.split_whitespace().next()?)
+}
+
+pub fn record_checksum(path: &Path, sha256: &str) -> Result<()> {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let sidecar = checksum_path(path);
+    std::fs::write(&sidecar, format!("{}  {}\n", sha256, name))
+        .map_err(|e| Error::io(format!("Failed to write {:?}", sidecar), e))
+}
+
+/// Hashes `path` and compares it with `pinned` (e.g. the catalog's checksum) or,
+/// without one, with the checksum recorded when it was downloaded. A pinned value
+/// wins over the recorded one, which could have been written for a tampered file.
+pub fn verify_file(path: &Path, pinned: Option<&str>) -> Result<ChecksumStatus> {
+    if !path.exists() {
+        return Ok(ChecksumStatus::Missing);
+    }
+    let actual = sha256_file(path)?;
+    let expected = pinned
+        .and_then(normalize_sha256)
+        .or_else(|| recorded_checksum(path));
+    Ok(match expected {
+        Some(expected) if expected == actual => ChecksumStatus::Ok { sha256: actual },
+        Some(expected) => ChecksumStatus::Mismatch { expected, actual },
+        None => ChecksumStatus::Unverified { sha256: actual },
+    })
+}
+
+/// Accepts a 64 character hex SHA-256, optionally quoted or marked as a weak ETag.
+pub(crate) fn normalize_sha256(value: &str) -> Option<String> {
+    let value = value.trim().trim_start_matches("W/").trim_matches('"');
+    if value.len() == 64 && value.chars().all(|c| c.is_ascii_hexdigit()) {
+        Some(value.to_ascii_lowercase())
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sha256_file() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let path = std::env::temp_dir().join("omni_tagger_test_sha256.txt");
+        std::fs::write(&path, b"abc")?;
+        assert_eq!(
+            sha256_file(&path)?,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_file() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let path = std::env::temp_dir().join("omni_tagger_test_verify.onnx");
+        let _ = std::fs::remove_file(checksum_path(&path));
+        std::fs::write(&path, b"abc")?;
+        assert!(matches!(
+            verify_file(&path, None)?,
+            ChecksumStatus::Unverified { .. }
+        ));
+
+        let abc = sha256_file(&path)?;
+        record_checksum(&path, &abc)?;
+        assert!(matches!(verify_file(&path, None)?, ChecksumStatus::Ok { .. }));
+
+        // Simulate a truncated file
+        std::fs::write(&path, b"ab")?;
+        assert!(matches!(
+            verify_file(&path, None)?,
+            ChecksumStatus::Mismatch { .. }
+        ));
+
+        // A sidecar rewritten to match a tampered file does not fool a pinned checksum
+        record_checksum(&path, &sha256_file(&path)?)?;
+        assert!(matches!(verify_file(&path, None)?, ChecksumStatus::Ok { .. }));
+        assert_eq!(
+            verify_file(&path, Some(&abc))?,
+            ChecksumStatus::Mismatch {
+                expected: abc.clone(),
+                actual: sha256_file(&path)?,
+            }
+        );
+
+        std::fs::remove_file(&path)?;
+        std::fs::remove_file(checksum_path(&path))?;
+        assert_eq!(verify_file(&path, None)?, ChecksumStatus::Missing);
+        Ok(())
+    }
+
+    #[test]
+    fn test_normalize_sha256() {
+        let hash = "BA7816BF8F01CFEA414140DE5DAE2223B00361A396177A9CB410FF61F20015AD";
+        assert_eq!(
+            normalize_sha256(&format!("\"{}\"", hash)),
+            Some(hash.to_ascii_lowercase())
+        );
+        assert_eq!(
+            normalize_sha256(&format!("W/\"{}\"", hash)),
+            Some(hash.to_ascii_lowercase())
+        );
+        // Git blob ids (SHA-1) are not content checksums
+        assert_eq!(
+            normalize_sha256("\"a9993e364706816aba3e25717850c26c9cd0d89d\""),
+            None
+        );
+    }
+}
@@ -0,0 +1,221 @@
+use crate::error::{Error, Result};
+use image::{DynamicImage, ImageError, ImageFormat, ImageReader, Limits};
+use std::io::{BufRead, BufReader, Cursor, Read, Seek};
+use std::path::Path;
+
+/// Formats accepted as input. Everything else is rejected before decoding, even if
+/// the `image` crate could read it.
+pub const SUPPORTED_FORMATS: [ImageFormat; 6] = [
+    ImageFormat::Png,
+    ImageFormat::Jpeg,
+    ImageFormat::WebP,
+    ImageFormat::Gif,
+    ImageFormat::Bmp,
+    ImageFormat::Tiff,
+];
+
+/// Largest width or height accepted, in pixels.
+pub const MAX_DIMENSION: u32 = 16_384;
+
+/// Upper bound for memory allocated while decoding a single image.
+pub const MAX_ALLOC: u64 = 512 * 1024 * 1024;
+
+/// Upper bound for image payloads read from standard input or the HTTP API. URL
+/// downloads are limited by `url_policy.max_size` instead.
+pub const MAX_INPUT_SIZE: u64 = 20 * 1024 * 1024; // 20 MB limit
+
+/// Decoder limits guarding against images that are small on disk but expand to
+/// huge pixel buffers (decompression bombs).
+pub fn limits() -> Limits {
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_DIMENSION);
+    limits.max_image_height = Some(MAX_DIMENSION);
+    limits.max_alloc = Some(MAX_ALLOC);
+    limits
+}
+
+/// Detects the format of `bytes` from their content, ignoring any file extension
+/// or declared content type, and checks it against [`SUPPORTED_FORMATS`].
+pub fn sniff_format(bytes: &[u8]) -> Result<ImageFormat> {
+    let format = image::guess_format(bytes)
+        .map_err(|_| Error::UnsupportedFormat("data is not a recognized image".to_string()))?;
+    check_format(format)?;
+    Ok(format)
+}
+
+fn check_format(format: ImageFormat) -> Result<()> {
+    if SUPPORTED_FORMATS.contains(&format) {
+        Ok(())
+    } else {
+        Err(Error::UnsupportedFormat(format!(
+            "{:?} images are not supported (expected PNG, JPEG, WebP, GIF, BMP or TIFF)",
+            format
+        )))
+    }
+}
+
+/// Checks the size of an image that is already decoded, such as a clipboard bitmap.
+pub fn check_dimensions(width: u32, height: u32) -> Result<()> {
+    if width > MAX_DIMENSION || height > MAX_DIMENSION {
+        return Err(Error::ImageTooLarge(format!(
+            "{}x{} exceeds the limit of {} pixels per side",
+            width, height, MAX_DIMENSION
+        )));
+    }
+    Ok(())
+}
+
+/// Decodes in-memory image data (URL downloads, data URIs, standard input, API
+/// uploads) with format sniffing and [`limits`] applied.
+pub fn decode_image(bytes: &[u8]) -> Result<DynamicImage> {
+    let format = sniff_format(bytes)?;
+    decode_with(
+        ImageReader::with_format(Cursor::new(bytes), format),
+        limits(),
+    )
+}
+
+/// Reads an image payload such as piped standard input, rejecting empty input and
+/// anything larger than [`MAX_INPUT_SIZE`].
+pub fn read_input<R: Read>(reader: R) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    reader
+        .take(MAX_INPUT_SIZE + 1)
+        .read_to_end(&mut bytes)
+        .map_err(|e| Error::io("Failed to read image data", e))?;
+
+    if bytes.len() as u64 > MAX_INPUT_SIZE {
+        return Err(Error::PayloadTooLarge {
+            limit: MAX_INPUT_SIZE,
+        });
+    }
+    if bytes.is_empty() {
+        return Err(Error::EmptyInput);
+    }
+    Ok(bytes)
+}
+
+/// Opens an image file like [`decode_image`]; the format is taken from the file
+/// content, not its extension.
+pub fn open_image(path: &Path) -> Result<DynamicImage> {
+    // `ImageReader::open` would fall back to the extension when sniffing fails
+    let reader = std::fs::File::open(path)
+        .and_then(|file| ImageReader::new(BufReader::new(file)).with_guessed_format())
+        .map_err(|e| Error::io(format!("Failed to read {:?}", path), e))?;
+    let format = reader
+        .format()
+        .ok_or_else(|| Error::UnsupportedFormat(format!("{:?} is not a recognized image", path)))?;
+    check_format(format)?;
+    decode_with(reader, limits())
+}
+
+fn decode_with<R: BufRead + Seek>(
+    mut reader: ImageReader<R>,
+    limits: Limits,
+) -> Result<DynamicImage> {
+    reader.limits(limits);
+    reader.decode().map_err(|e| match e {
+        ImageError::Limits(e) => Error::ImageTooLarge(e.to_string()),
+        e => Error::ImageDecode(e),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(width: u32, height: u32, format: ImageFormat) -> Vec<u8> {
+        let mut bytes = Cursor::new(Vec::new());
+        DynamicImage::new_rgb8(width, height)
+            .write_to(&mut bytes, format)
+            .unwrap();
+        bytes.into_inner()
+    }
+
+    #[test]
+    fn test_decode_sniffs_format() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let img = decode_image(&encode(8, 4, ImageFormat::Png))?;
+        assert_eq!((img.width(), img.height()), (8, 4));
+        assert_eq!(
+            sniff_format(&encode(2, 2, ImageFormat::Jpeg))?,
+            ImageFormat::Jpeg
+        );
+
+        assert!(matches!(
+            decode_image(b"<html><body>Not found</body></html>"),
+            Err(Error::UnsupportedFormat(_))
+        ));
+        assert!(matches!(
+            sniff_format(&encode(2, 2, ImageFormat::Ico)),
+            Err(Error::UnsupportedFormat(_))
+        ));
+
+        // A truncated image is a decode error, not a format error
+        let png = encode(8, 8, ImageFormat::Png);
+        assert!(matches!(
+            decode_image(&png[..png.len() / 2]),
+            Err(Error::ImageDecode(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_input_limits() {
+        let data = vec![1u8; 16];
+        assert_eq!(read_input(&data[..]).unwrap(), data);
+
+        assert!(matches!(read_input(std::io::empty()), Err(Error::EmptyInput)));
+
+        let oversized = std::io::repeat(0).take(MAX_INPUT_SIZE + 1);
+        assert!(matches!(
+            read_input(oversized),
+            Err(Error::PayloadTooLarge {
+                limit: MAX_INPUT_SIZE
+            })
+        ));
+    }
+
+    #[test]
+    fn test_open_image_ignores_extension() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let path = std::env::temp_dir().join("omni_tagger_test_decode_png.jpg");
+        std::fs::write(&path, encode(3, 5, ImageFormat::Png))?;
+        let img = open_image(&path)?;
+        assert_eq!((img.width(), img.height()), (3, 5));
+
+        std::fs::write(&path, b"plain text")?;
+        assert!(matches!(
+            open_image(&path),
+            Err(Error::UnsupportedFormat(_))
+        ));
+
+        std::fs::remove_file(path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_limits_reject_oversized_images() {
+        let png = encode(64, 32, ImageFormat::Png);
+
+        let mut small = limits();
+        small.max_image_width = Some(32);
+        let reader = ImageReader::with_format(Cursor::new(&png[..]), ImageFormat::Png);
+        assert!(matches!(
+            decode_with(reader, small),
+            Err(Error::ImageTooLarge(_))
+        ));
+
+        let mut small = limits();
+        small.max_alloc = Some(1024);
+        let reader = ImageReader::with_format(Cursor::new(&png[..]), ImageFormat::Png);
+        assert!(matches!(
+            decode_with(reader, small),
+            Err(Error::ImageTooLarge(_))
+        ));
+
+        assert!(check_dimensions(MAX_DIMENSION, 1).is_ok());
+        assert!(matches!(
+            check_dimensions(50_000, 50_000),
+            Err(Error::ImageTooLarge(_))
+        ));
+    }
+}
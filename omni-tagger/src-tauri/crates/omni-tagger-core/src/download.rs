@@ -0,0 +1,794 @@
+use crate::checksum::{normalize_sha256, record_checksum, sha256_file};
+use crate::error::{Error, Result};
+use crate::network::NetworkConfig;
+use futures_util::StreamExt;
+use reqwest::StatusCode;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tokio::fs::File;
+use tokio::io::AsyncWriteExt;
+use tokio_util::sync::CancellationToken;
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct DownloadProgress {
+    pub file: String,
+    /// Size of the file in bytes, or 0 when the server does not report it.
+    pub total: u64,
+    pub downloaded: u64,
+    pub percent: f64,
+    /// Average transfer rate of the current attempt.
+    pub bytes_per_sec: f64,
+    /// Estimated seconds remaining; `None` while the size or rate is unknown.
+    pub eta_secs: Option<f64>,
+}
+
+impl DownloadProgress {
+    /// `session_bytes` is what arrived since `started`, excluding resumed data.
+    fn new(file: &str, total: u64, downloaded: u64, session_bytes: u64, elapsed: Duration) -> Self {
+        let secs = elapsed.as_secs_f64();
+        let bytes_per_sec = if secs > 0.0 {
+            session_bytes as f64 / secs
+        } else {
+            0.0
+        };
+        let (percent, eta_secs) = if total > 0 {
+            let remaining = total.saturating_sub(downloaded) as f64;
+            let eta = (bytes_per_sec > 0.0).then(|| remaining / bytes_per_sec);
+            ((downloaded as f64 / total as f64) * 100.0, eta)
+        } else {
+            (0.0, None)
+        };
+        Self {
+            file: file.to_string(),
+            total,
+            downloaded,
+            percent,
+            bytes_per_sec,
+            eta_secs,
+        }
+    }
+
+    /// True when the total size is unknown and only the byte count can be shown.
+    pub fn is_indeterminate(&self) -> bool {
+        self.total == 0
+    }
+}
+
+/// Returns the SHA-256 the server publishes for `url`.
+///
+/// Hugging Face answers file requests with a redirect whose `X-Linked-Etag` header
+/// is the SHA-256 of files stored in LFS (the model weights). Small files kept in
+/// git carry a SHA-1 blob id instead, which is ignored.
+///
+/// The request is given up after [`HEAD_TIMEOUT`], so an unresponsive server only
+/// delays the download itself; cancelling `cancel` stops it with
+/// [`Error::Cancelled`].
+pub async fn published_checksum(
+    url: &str,
+    network: &NetworkConfig,
+    cancel: &CancellationToken,
+) -> Result<Option<String>> {
+    let client = network
+        .client_builder()?
+        .redirect(reqwest::redirect::Policy::none())
+        .timeout(HEAD_TIMEOUT)
+        .build()?;
+    let res = tokio::select! {
+        res = client.head(url).send() => res,
+        _ = cancel.cancelled() => return Err(Error::Cancelled),
+    };
+    let Ok(res) = res else { return Ok(None) };
+    let headers = res.headers();
+    Ok(headers
+        .get("x-linked-etag")
+        .or_else(|| headers.get(reqwest::header::ETAG))
+        .and_then(|v| v.to_str().ok())
+        .and_then(normalize_sha256))
+}
+
+/// Time limit for the `HEAD` request in [`published_checksum`].
+pub const HEAD_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// How often a failed download is retried before giving up.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    /// Delay before the first retry; doubled after every further attempt.
+    pub initial_backoff: Duration,
+    /// An attempt that cannot connect within this time fails and is retried.
+    pub connect_timeout: Duration,
+    /// An attempt that receives no data for this long fails and is retried, so a
+    /// stalled connection does not hang the download forever.
+    pub read_timeout: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 4,
+            initial_backoff: Duration::from_secs(1),
+            connect_timeout: Duration::from_secs(15),
+            read_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Path of the temporary file a download is written to before it is complete.
+pub fn part_path(dest: &Path) -> PathBuf {
+    let mut name = dest.as_os_str().to_owned();
+    name.push(".part");
+    PathBuf::from(name)
+}
+
+/// Path of the file holding the `ETag` or `Last-Modified` value of the response the
+/// `.part` file was written from. Resuming sends it as `If-Range`, so a file that
+/// changed on the server in the meantime is downloaded again instead of being
+/// spliced onto the old data.
+fn validator_path(dest: &Path) -> PathBuf {
+    let mut name = part_path(dest).into_os_string();
+    name.push(".validator");
+    PathBuf::from(name)
+}
+
+/// Returns the value to send as `If-Range` for `res`: a strong `ETag`, or else
+/// `Last-Modified`. Weak ETags may not be used with `If-Range`.
+fn range_validator(res: &reqwest::Response) -> Option<String> {
+    let headers = res.headers();
+    let etag = headers
+        .get(reqwest::header::ETAG)
+        .and_then(|v| v.to_str().ok())
+        .filter(|v| !v.starts_with("W/"));
+    etag.or_else(|| {
+        headers
+            .get(reqwest::header::LAST_MODIFIED)
+            .and_then(|v| v.to_str().ok())
+    })
+    .map(str::to_string)
+}
+
+/// Streams `url` into `dest` with the default [`RetryPolicy`], calling
+/// `on_progress` after every chunk.
+pub async fn download_with_progress<F>(
+    url: &str,
+    dest: &Path,
+    network: &NetworkConfig,
+    on_progress: F,
+) -> Result<()>
+where
+    F: FnMut(&DownloadProgress),
+{
+    download_with_retry(
+        url,
+        dest,
+        None,
+        network,
+        &RetryPolicy::default(),
+        &CancellationToken::new(),
+        on_progress,
+    )
+    .await
+}
+
+/// Downloads `url` into `dest` atomically.
+///
+/// Data is written to `<dest>.part` and only renamed to `dest` once it is complete
+/// and matches the published checksum, so an interrupted download never leaves a
+/// truncated model behind. Interrupted transfers are retried with exponential
+/// backoff and resume from the `.part` file with an HTTP `Range` request, also
+/// across application restarts. The request carries `If-Range`, so the transfer
+/// starts over if the file changed on the server. Connections that cannot be
+/// established or stop delivering data within the timeouts of `policy` count as
+/// failed attempts. The checksum is recorded next to the file so it can
+/// be verified again later.
+///
+/// `expected_sha256` (e.g. pinned in the model catalog) takes precedence over the
+/// checksum published by the server, which comes from the same server as the file
+/// and therefore only guards against transfer errors.
+///
+/// Requests go through the proxy and trust the extra certificates in `network`.
+///
+/// Cancelling `cancel` stops the transfer with [`Error::Cancelled`]; the `.part`
+/// file is kept so the download can be resumed later.
+pub async fn download_with_retry<F>(
+    url: &str,
+    dest: &Path,
+    expected_sha256: Option<&str>,
+    network: &NetworkConfig,
+    policy: &RetryPolicy,
+    cancel: &CancellationToken,
+    mut on_progress: F,
+) -> Result<()>
+where
+    F: FnMut(&DownloadProgress),
+{
+    // Create parent directory if it doesn't exist
+    if let Some(parent) = dest.parent() {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .map_err(|e| Error::io("Failed to create directory", e))?;
+    }
+
+    let expected = match expected_sha256.and_then(normalize_sha256) {
+        Some(expected) => Some(expected),
+        None => {
+            let published = published_checksum(url, network, cancel).await?;
+            if published.is_none() {
+                eprintln!(
+                    "No checksum is pinned or published for {}; the download cannot be verified",
+                    url
+                );
+            }
+            published
+        }
+    };
+    let part = part_path(dest);
+    let client = network
+        .client_builder()?
+        .connect_timeout(policy.connect_timeout)
+        .read_timeout(policy.read_timeout)
+        .build()?;
+    let mut backoff = policy.initial_backoff;
+    let mut attempt = 1;
+
+    loop {
+        match fetch_to_part(&client, url, &part, dest, cancel, &mut on_progress).await {
+            Ok(()) => break,
+            Err(e) if attempt < policy.max_attempts && is_transient(&e) => {
+                eprintln!(
+                    "Download of {} failed (attempt {}/{}): {}. Retrying in {:?}",
+                    url, attempt, policy.max_attempts, e, backoff
+                );
+                tokio::select! {
+                    _ = tokio::time::sleep(backoff) => {}
+                    _ = cancel.cancelled() => return Err(Error::Cancelled),
+                }
+                backoff *= 2;
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    // Hashing a model takes a few seconds; keep it off the async worker threads
+    let part_to_hash = part.clone();
+    let actual = tokio::task::spawn_blocking(move || sha256_file(&part_to_hash))
+        .await
+        .map_err(|e| Error::io("Failed to hash download", std::io::Error::other(e)))??;
+    if let Some(expected) = expected {
+        if expected != actual {
+            // A corrupt partial file cannot be resumed; start from scratch next time
+            let _ = tokio::fs::remove_file(&part).await;
+            let _ = tokio::fs::remove_file(validator_path(dest)).await;
+            return Err(Error::ChecksumMismatch {
+                path: dest.to_path_buf(),
+                expected,
+                actual,
+            });
+        }
+    }
+
+    tokio::fs::rename(&part, dest).await.map_err(|e| {
+        Error::io(
+            format!("Failed to move download into place at {:?}", dest),
+            e,
+        )
+    })?;
+    let _ = tokio::fs::remove_file(validator_path(dest)).await;
+    record_checksum(dest, &actual)
+}
+
+/// Network failures, timeouts, truncated transfers, rate limiting and server errors
+/// are worth retrying; other client errors (e.g. 404) and local I/O errors are not.
+pub(crate) fn is_transient(err: &Error) -> bool {
+    match err {
+        Error::IncompleteDownload { .. } | Error::Timeout { .. } => true,
+        Error::Http(e) => match e.status() {
+            Some(status) => {
+                status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+            }
+            None => true,
+        },
+        _ => false,
+    }
+}
+
+/// Appends the missing part of `url` to `part`, resuming from its current length.
+async fn fetch_to_part<F>(
+    client: &reqwest::Client,
+    url: &str,
+    part: &Path,
+    dest: &Path,
+    cancel: &CancellationToken,
+    on_progress: &mut F,
+) -> Result<()>
+where
+    F: FnMut(&DownloadProgress),
+{
+    let validator_file = validator_path(dest);
+    let mut existing = tokio::fs::metadata(part)
+        .await
+        .map(|m| m.len())
+        .unwrap_or(0);
+    let validator = tokio::fs::read_to_string(&validator_file).await.ok();
+
+    let mut request = client.get(url);
+    match validator {
+        Some(validator) if existing > 0 => {
+            request = request
+                .header(reqwest::header::RANGE, format!("bytes={}-", existing))
+                .header(reqwest::header::IF_RANGE, validator.trim());
+        }
+        // Without a validator there is no telling whether the partial data still
+        // belongs to the file on the server
+        _ => existing = 0,
+    }
+    let res = tokio::select! {
+        res = request.send() => res?,
+        _ = cancel.cancelled() => return Err(Error::Cancelled),
+    };
+
+    if res.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+        // The partial file is at least as long as the resource; it is either complete
+        // or stale, so fetch the whole file again.
+        tokio::fs::remove_file(part)
+            .await
+            .map_err(|e| Error::io("Failed to remove partial download", e))?;
+        let _ = tokio::fs::remove_file(&validator_file).await;
+        return Box::pin(fetch_to_part(client, url, part, dest, cancel, on_progress)).await;
+    }
+    let res = res.error_for_status()?;
+
+    // Servers without range support, and servers whose file no longer matches the
+    // validator, answer with the full body
+    let resumed = existing > 0 && res.status() == StatusCode::PARTIAL_CONTENT;
+    let offset = if resumed { existing } else { 0 };
+    let total_size = res.content_length().map(|len| len + offset).unwrap_or(0);
+
+    let mut file = if resumed {
+        tokio::fs::OpenOptions::new().append(true).open(part).await
+    } else {
+        File::create(part).await
+    }
+    .map_err(|e| Error::io("Failed to create file", e))?;
+    if !resumed {
+        match range_validator(&res) {
+            Some(validator) => tokio::fs::write(&validator_file, validator).await,
+            None => match tokio::fs::remove_file(&validator_file).await {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+                _ => Ok(()),
+            },
+        }
+        .map_err(|e| Error::io("Failed to record download validator", e))?;
+    }
+    let mut stream = res.bytes_stream();
+    let mut downloaded: u64 = offset;
+    let started = Instant::now();
+
+    let filename = dest
+        .file_name()
+        .and_then(|s| s.to_str())
+        .unwrap_or("unknown")
+        .to_string();
+
+    loop {
+        let item = tokio::select! {
+            item = stream.next() => item,
+            _ = cancel.cancelled() => {
+                let _ = file.flush().await;
+                return Err(Error::Cancelled);
+            }
+        };
+        let Some(item) = item else { break };
+        let chunk = match item {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                // Keep what arrived so the retry can resume from it
+                let _ = file.flush().await;
+                return Err(e.into());
+            }
+        };
+        file.write_all(&chunk)
+            .await
+            .map_err(|e| Error::io("Error while writing to file", e))?;
+
+        downloaded += chunk.len() as u64;
+
+        on_progress(&DownloadProgress::new(
+            &filename,
+            total_size,
+            downloaded,
+            downloaded - offset,
+            started.elapsed(),
+        ));
+    }
+    file.flush()
+        .await
+        .map_err(|e| Error::io("Error while writing to file", e))?;
+
+    if total_size > 0 && downloaded < total_size {
+        return Err(Error::IncompleteDownload {
+            expected: total_size,
+            received: downloaded,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checksum::{checksum_path, recorded_checksum};
+    use std::sync::{Arc, Mutex};
+    use tokio::io::AsyncReadExt;
+    use tokio::net::TcpListener;
+
+    const ABC_SHA256: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+    const BODY: &[u8] = b"abcdefgh";
+    const BODY_SHA256: &str = "9c56cc51b374c3ba189210d5b6d4bf57790d351c96c47c02190ecf1e430635ab";
+
+    const NO_DELAY: RetryPolicy = RetryPolicy {
+        max_attempts: 3,
+        initial_backoff: Duration::ZERO,
+        connect_timeout: Duration::from_secs(5),
+        read_timeout: Duration::from_secs(5),
+    };
+
+    /// Strong ETag of the file served by [`serve`].
+    const FILE_ETAG: &str = "\"v1\"";
+
+    const DIRECT: NetworkConfig = NetworkConfig {
+        proxy: String::new(),
+        no_proxy: Vec::new(),
+        ca_bundle: String::new(),
+    };
+
+    /// Local HTTP stub serving `body`. HEAD requests get `etag` as `X-Linked-Etag`,
+    /// GET requests carry [`FILE_ETAG`] and honour `Range: bytes=N-` unless `If-Range`
+    /// names another version, and the first `truncate` GET responses are cut off
+    /// halfway; with `stall` the connection is then held open instead of closed.
+    /// Returns the URL and the `Range` headers received.
+    async fn serve(
+        etag: &'static str,
+        body: &'static [u8],
+        truncate: usize,
+        stall: bool,
+    ) -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let ranges = Arc::new(Mutex::new(Vec::new()));
+        let seen = Arc::clone(&ranges);
+        tokio::spawn(async move {
+            let mut gets = 0;
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut request = Vec::new();
+                let mut buf = [0u8; 1024];
+                while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                    match socket.read(&mut buf).await {
+                        Ok(0) | Err(_) => break,
+                        Ok(n) => request.extend_from_slice(&buf[..n]),
+                    }
+                }
+                let request = String::from_utf8_lossy(&request).to_lowercase();
+
+                if request.starts_with("head") {
+                    let head = format!(
+                        "HTTP/1.1 200 OK\r\nX-Linked-Etag: \"{}\"\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                        etag
+                    );
+                    let _ = socket.write_all(head.as_bytes()).await;
+                    continue;
+                }
+
+                let current = request
+                    .lines()
+                    .find_map(|l| l.strip_prefix("if-range:"))
+                    .is_none_or(|v| v.trim() == FILE_ETAG);
+                let start = request
+                    .lines()
+                    .find_map(|l| l.strip_prefix("range: bytes="))
+                    .map(|r| {
+                        seen.lock().unwrap().push(format!("bytes={}", r.trim()));
+                        r.trim().trim_end_matches('-').parse::<usize>().unwrap()
+                    })
+                    .filter(|_| current);
+                let head = match start {
+                    Some(start) if start >= body.len() => {
+                        "HTTP/1.1 416 Range Not Satisfiable\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string()
+                    }
+                    Some(start) => format!(
+                        "HTTP/1.1 206 Partial Content\r\nETag: {}\r\nContent-Range: bytes {}-{}/{}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                        FILE_ETAG,
+                        start,
+                        body.len() - 1,
+                        body.len(),
+                        body.len() - start
+                    ),
+                    None => format!(
+                        "HTTP/1.1 200 OK\r\nETag: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                        FILE_ETAG,
+                        body.len()
+                    ),
+                };
+                let payload = match start {
+                    Some(start) if start >= body.len() => &body[..0],
+                    Some(start) => &body[start..],
+                    None => body,
+                };
+                gets += 1;
+                let truncated = gets <= truncate;
+                let payload = if truncated {
+                    &payload[..payload.len() / 2]
+                } else {
+                    payload
+                };
+                let _ = socket.write_all(head.as_bytes()).await;
+                let _ = socket.write_all(payload).await;
+                if truncated && stall {
+                    tokio::spawn(async move {
+                        tokio::time::sleep(Duration::from_secs(60)).await;
+                        drop(socket);
+                    });
+                }
+            }
+        });
+        (format!("http://{}/model.onnx", addr), ranges)
+    }
+
+    fn cleanup(dest: &Path) {
+        let _ = std::fs::remove_file(dest);
+        let _ = std::fs::remove_file(part_path(dest));
+        let _ = std::fs::remove_file(validator_path(dest));
+        let _ = std::fs::remove_file(checksum_path(dest));
+    }
+
+    #[tokio::test]
+    async fn test_download_records_checksum() -> std::result::Result<(), Box<dyn std::error::Error>>
+    {
+        let (url, _) = serve(ABC_SHA256, b"abc", 0, false).await;
+        let dest = std::env::temp_dir().join("omni_tagger_test_download_ok.onnx");
+        cleanup(&dest);
+
+        download_with_progress(&url, &dest, &DIRECT, |_| {}).await?;
+        assert_eq!(std::fs::read(&dest)?, b"abc");
+        assert_eq!(recorded_checksum(&dest).as_deref(), Some(ABC_SHA256));
+        assert!(!part_path(&dest).exists());
+
+        cleanup(&dest);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_download_rejects_checksum_mismatch() {
+        let (url, _) = serve(ABC_SHA256, b"abd", 0, false).await;
+        let dest = std::env::temp_dir().join("omni_tagger_test_download_bad.onnx");
+        cleanup(&dest);
+
+        let result = download_with_progress(&url, &dest, &DIRECT, |_| {}).await;
+        assert!(matches!(result, Err(Error::ChecksumMismatch { .. })));
+        assert!(!dest.exists());
+        assert!(!part_path(&dest).exists());
+        assert!(!checksum_path(&dest).exists());
+    }
+
+    #[tokio::test]
+    async fn test_expected_checksum_overrides_published() {
+        let (url, _) = serve(ABC_SHA256, b"abc", 0, false).await;
+        let dest = std::env::temp_dir().join("omni_tagger_test_download_expected.onnx");
+        cleanup(&dest);
+
+        let cancel = CancellationToken::new();
+        let result = download_with_retry(
+            &url,
+            &dest,
+            Some(BODY_SHA256),
+            &DIRECT,
+            &NO_DELAY,
+            &cancel,
+            |_| {},
+        )
+        .await;
+        assert!(matches!(result, Err(Error::ChecksumMismatch { .. })));
+        assert!(!dest.exists());
+    }
+
+    #[tokio::test]
+    async fn test_download_retries_and_resumes(
+    ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let (url, ranges) = serve(BODY_SHA256, BODY, 1, false).await;
+        let dest = std::env::temp_dir().join("omni_tagger_test_download_retry.onnx");
+        cleanup(&dest);
+
+        let mut last = 0;
+        download_with_retry(
+            &url,
+            &dest,
+            None,
+            &DIRECT,
+            &NO_DELAY,
+            &CancellationToken::new(),
+            |p| last = p.downloaded,
+        )
+        .await?;
+        assert_eq!(std::fs::read(&dest)?, BODY);
+        assert_eq!(last, BODY.len() as u64);
+        assert_eq!(*ranges.lock().unwrap(), vec!["bytes=4-".to_string()]);
+
+        cleanup(&dest);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_interrupted_download_is_not_treated_as_complete(
+    ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let (url, ranges) = serve(BODY_SHA256, BODY, 1, false).await;
+        let dest = std::env::temp_dir().join("omni_tagger_test_download_resume.onnx");
+        cleanup(&dest);
+
+        let once = RetryPolicy {
+            max_attempts: 1,
+            ..NO_DELAY
+        };
+        let cancel = CancellationToken::new();
+        let result = download_with_retry(&url, &dest, None, &DIRECT, &once, &cancel, |_| {}).await;
+        assert!(result.is_err());
+        assert!(!dest.exists());
+        assert_eq!(std::fs::read(part_path(&dest))?, &BODY[..4]);
+
+        // A later attempt (e.g. after a restart) picks up where the last one stopped
+        download_with_retry(&url, &dest, None, &DIRECT, &once, &cancel, |_| {}).await?;
+        assert_eq!(std::fs::read(&dest)?, BODY);
+        assert_eq!(*ranges.lock().unwrap(), vec!["bytes=4-".to_string()]);
+
+        cleanup(&dest);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stale_part_file_is_restarted(
+    ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let (url, _) = serve(ABC_SHA256, b"abc", 0, false).await;
+        let dest = std::env::temp_dir().join("omni_tagger_test_download_stale.onnx");
+        cleanup(&dest);
+        std::fs::write(part_path(&dest), b"abcdef")?;
+        std::fs::write(validator_path(&dest), FILE_ETAG)?;
+
+        let cancel = CancellationToken::new();
+        download_with_retry(&url, &dest, None, &DIRECT, &NO_DELAY, &cancel, |_| {}).await?;
+        assert_eq!(std::fs::read(&dest)?, b"abc");
+
+        cleanup(&dest);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_resume_sends_if_range() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let (url, _) = serve(BODY_SHA256, BODY, 1, false).await;
+        let dest = std::env::temp_dir().join("omni_tagger_test_download_if_range.onnx");
+        cleanup(&dest);
+
+        let once = RetryPolicy {
+            max_attempts: 1,
+            ..NO_DELAY
+        };
+        let cancel = CancellationToken::new();
+        let result = download_with_retry(&url, &dest, None, &DIRECT, &once, &cancel, |_| {}).await;
+        assert!(result.is_err());
+        assert_eq!(std::fs::read_to_string(validator_path(&dest))?, FILE_ETAG);
+
+        download_with_retry(&url, &dest, None, &DIRECT, &once, &cancel, |_| {}).await?;
+        assert_eq!(std::fs::read(&dest)?, BODY);
+        assert!(!validator_path(&dest).exists());
+
+        cleanup(&dest);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_changed_file_is_downloaded_again(
+    ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let (url, ranges) = serve(BODY_SHA256, BODY, 0, false).await;
+        let dest = std::env::temp_dir().join("omni_tagger_test_download_changed.onnx");
+        cleanup(&dest);
+        // Half of an older version of the file
+        std::fs::write(part_path(&dest), b"ABCD")?;
+        std::fs::write(validator_path(&dest), "\"v0\"")?;
+
+        let cancel = CancellationToken::new();
+        download_with_retry(&url, &dest, None, &DIRECT, &NO_DELAY, &cancel, |_| {}).await?;
+        assert_eq!(std::fs::read(&dest)?, BODY);
+        assert_eq!(*ranges.lock().unwrap(), vec!["bytes=4-".to_string()]);
+
+        cleanup(&dest);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_part_file_without_validator_is_restarted(
+    ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let (url, ranges) = serve(BODY_SHA256, BODY, 0, false).await;
+        let dest = std::env::temp_dir().join("omni_tagger_test_download_no_validator.onnx");
+        cleanup(&dest);
+        std::fs::write(part_path(&dest), b"ABCD")?;
+
+        let cancel = CancellationToken::new();
+        download_with_retry(&url, &dest, None, &DIRECT, &NO_DELAY, &cancel, |_| {}).await?;
+        assert_eq!(std::fs::read(&dest)?, BODY);
+        assert!(ranges.lock().unwrap().is_empty());
+
+        cleanup(&dest);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stalled_download_is_retried(
+    ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let (url, ranges) = serve(BODY_SHA256, BODY, 1, true).await;
+        let dest = std::env::temp_dir().join("omni_tagger_test_download_stall.onnx");
+        cleanup(&dest);
+
+        let policy = RetryPolicy {
+            read_timeout: Duration::from_millis(200),
+            ..NO_DELAY
+        };
+        let cancel = CancellationToken::new();
+        download_with_retry(&url, &dest, None, &DIRECT, &policy, &cancel, |_| {}).await?;
+        assert_eq!(std::fs::read(&dest)?, BODY);
+        assert_eq!(*ranges.lock().unwrap(), vec!["bytes=4-".to_string()]);
+
+        cleanup(&dest);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_published_checksum_is_cancellable() {
+        // Accepts the connection but never answers
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/model.onnx", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let mut sockets = Vec::new();
+            while let Ok((socket, _)) = listener.accept().await {
+                sockets.push(socket);
+            }
+        });
+
+        let cancel = CancellationToken::new();
+        let canceller = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            canceller.cancel();
+        });
+        let result = published_checksum(&url, &DIRECT, &cancel).await;
+        assert!(matches!(result, Err(Error::Cancelled)));
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_download() {
+        let (url, _) = serve(BODY_SHA256, BODY, 0, false).await;
+        let dest = std::env::temp_dir().join("omni_tagger_test_download_cancel.onnx");
+        cleanup(&dest);
+
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        let result =
+            download_with_retry(&url, &dest, None, &DIRECT, &NO_DELAY, &cancel, |_| {}).await;
+        assert!(matches!(result, Err(Error::Cancelled)));
+        assert!(!dest.exists());
+
+        cleanup(&dest);
+    }
+
+    #[test]
+    fn test_download_progress_rate_and_eta() {
+        // 300 of 1000 bytes, 200 of them in the last two seconds (100 were resumed)
+        let progress = DownloadProgress::new("model.onnx", 1000, 300, 200, Duration::from_secs(2));
+        assert_eq!(progress.percent, 30.0);
+        assert_eq!(progress.bytes_per_sec, 100.0);
+        assert_eq!(progress.eta_secs, Some(7.0));
+        assert!(!progress.is_indeterminate());
+
+        let progress = DownloadProgress::new("tags.csv", 0, 500, 500, Duration::from_secs(1));
+        assert!(progress.is_indeterminate());
+        assert_eq!(progress.percent, 0.0);
+        assert_eq!(progress.eta_secs, None);
+    }
+}
@@ -0,0 +1,86 @@
+use std::path::PathBuf;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Failed to load tags file {path:?}: {message}")]
+    TagsFile { path: PathBuf, message: String },
+
+    #[error("Failed to load model {path:?}: {message}")]
+    ModelLoad { path: PathBuf, message: String },
+
+    #[error("Inference failed: {0}")]
+    Inference(String),
+
+    #[error("Failed to decode image: {0}")]
+    ImageDecode(#[from] image::ImageError),
+
+    #[error("Unsupported image format: {0}")]
+    UnsupportedFormat(String),
+
+    /// The image exceeds the decoder's dimension or memory limits.
+    #[error("Image too large to decode: {0}")]
+    ImageTooLarge(String),
+
+    #[error("Invalid URL: {0}")]
+    InvalidUrl(String),
+
+    /// The URL points at a local or private destination (SSRF protection).
+    #[error("Blocked URL: {0}")]
+    BlockedUrl(String),
+
+    #[error("Payload exceeds maximum allowed size of {limit} bytes")]
+    PayloadTooLarge { limit: u64 },
+
+    #[error("No image data received")]
+    EmptyInput,
+
+    #[error("Checksum mismatch for {path:?}: expected {expected}, got {actual}")]
+    ChecksumMismatch {
+        path: PathBuf,
+        expected: String,
+        actual: String,
+    },
+
+    #[error("Invalid model repository: {0}")]
+    InvalidRepo(String),
+
+    #[error("Invalid model bundle: {0}")]
+    InvalidBundle(String),
+
+    #[error("Invalid model catalog: {0}")]
+    Catalog(String),
+
+    /// Proxy or CA bundle settings that cannot be used.
+    #[error("Invalid network settings: {0}")]
+    Network(String),
+
+    #[error("Download incomplete: received {received} of {expected} bytes")]
+    IncompleteDownload { expected: u64, received: u64 },
+
+    #[error("Download cancelled")]
+    Cancelled,
+
+    #[error("Request timed out after {secs} s")]
+    Timeout { secs: u64 },
+
+    #[error("Request failed: {0}")]
+    Http(#[from] reqwest::Error),
+
+    #[error("{context}: {source}")]
+    Io {
+        context: String,
+        #[source]
+        source: std::io::Error,
+    },
+}
+
+impl Error {
+    pub(crate) fn io(context: impl Into<String>, source: std::io::Error) -> Self {
+        Self::Io {
+            context: context.into(),
+            source,
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,817 @@
+use crate::download::{is_transient, RetryPolicy};
+use crate::error::{Error, Result};
+use crate::network::NetworkConfig;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+use url::{Host, Url};
+
+/// Which image URLs may be fetched, and how much of them.
+///
+/// The defaults only allow public addresses on any domain. Loopback, link-local
+/// (including cloud metadata endpoints) and the other special-purpose ranges stay
+/// blocked regardless of these settings.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct UrlPolicy {
+    /// When non-empty, only these domains (and their subdomains) or IP addresses
+    /// may be fetched.
+    pub allow_domains: Vec<String>,
+    /// Domains (and their subdomains) or IP addresses that are never fetched. Takes
+    /// precedence over `allow_domains`.
+    pub deny_domains: Vec<String>,
+    /// Also allows private networks (10/8, 172.16/12, 192.168/16, 100.64/10 and
+    /// IPv6 unique local addresses), e.g. for a NAS on the LAN.
+    pub allow_private_networks: bool,
+    /// Largest response body accepted, in bytes.
+    pub max_size: u64,
+    /// Time limit for the whole fetch, from name resolution to the last byte and
+    /// including all retries, in seconds.
+    pub timeout_secs: u64,
+    /// Time limit for establishing the connection, in seconds.
+    pub connect_timeout_secs: u64,
+    /// How often a request that failed with a network error, timeout or server
+    /// error is tried again.
+    pub retries: u32,
+}
+
+impl Default for UrlPolicy {
+    fn default() -> Self {
+        Self {
+            allow_domains: Vec::new(),
+            deny_domains: Vec::new(),
+            allow_private_networks: false,
+            max_size: 20 * 1024 * 1024, // 20 MB limit
+            timeout_secs: 30,
+            connect_timeout_secs: 10,
+            retries: 2,
+        }
+    }
+}
+
+impl UrlPolicy {
+    /// Every fetch has a deadline, so a stalled server cannot hold up the
+    /// processing queue indefinitely.
+    fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout_secs.max(1))
+    }
+
+    fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: self.retries.saturating_add(1),
+            initial_backoff: RETRY_BACKOFF,
+            ..RetryPolicy::default()
+        }
+    }
+
+    fn check_host(&self, host: &str) -> Result<()> {
+        if self.deny_domains.iter().any(|d| host_matches(host, d)) {
+            return Err(Error::BlockedUrl(format!("{} is on the deny list", host)));
+        }
+        if !self.allow_domains.is_empty()
+            && !self.allow_domains.iter().any(|d| host_matches(host, d))
+        {
+            return Err(Error::BlockedUrl(format!("{} is not on the allow list", host)));
+        }
+        Ok(())
+    }
+
+    /// Describes why connecting to `ip` is not allowed, or `None` if it is.
+    fn blocked_reason(&self, ip: IpAddr) -> Option<&'static str> {
+        if self.allow_private_networks && is_private_network(ip) {
+            return None;
+        }
+        restricted_ip_reason(ip)
+    }
+}
+
+/// True if `host` is `pattern` or one of its subdomains. Patterns may be written
+/// as `example.com`, `.example.com` or `*.example.com`.
+fn host_matches(host: &str, pattern: &str) -> bool {
+    let host = host.trim_end_matches('.').to_lowercase();
+    let pattern = pattern
+        .trim()
+        .trim_start_matches("*.")
+        .trim_start_matches('.')
+        .trim_end_matches('.')
+        .to_lowercase();
+    !pattern.is_empty()
+        && (host == pattern
+            || host
+                .strip_suffix(pattern.as_str())
+                .is_some_and(|rest| rest.ends_with('.')))
+}
+
+/// Checks that `url` is an http(s) URL that does not name a local host or a
+/// restricted IP address and passes the domain lists of `policy`, to prevent SSRF
+/// through user-supplied image URLs.
+///
+/// Host names are checked once they are resolved, by [`resolve_public`] and by the
+/// resolver of the client [`fetch_bytes`] uses.
+pub fn validate_url(url: &str, policy: &UrlPolicy) -> Result<Url> {
+    let parsed_url = Url::parse(url).map_err(|e| Error::InvalidUrl(e.to_string()))?;
+
+    // 1. Check Scheme (only http/https)
+    let scheme = parsed_url.scheme();
+    if scheme != "http" && scheme != "https" {
+        return Err(Error::InvalidUrl(
+            "Invalid URL scheme. Only HTTP and HTTPS are allowed.".to_string(),
+        ));
+    }
+
+    // 2. Check Host (Reject localhost, loopback, private networks)
+    if let Some(host) = parsed_url.host_str() {
+        policy.check_host(host.trim_start_matches('[').trim_end_matches(']'))?;
+    }
+    match parsed_url.host() {
+        None => return Err(Error::InvalidUrl("URL has no host".to_string())),
+        Some(Host::Domain(domain)) => {
+            // Simple blocklist for common local hostnames
+            let lower_host = domain.trim_end_matches('.').to_lowercase();
+            if lower_host == "localhost"
+                || lower_host.ends_with(".localhost")
+                || lower_host == "broadcasthost"
+            {
+                return Err(Error::BlockedUrl(
+                    "URL resolves to a restricted local hostname".to_string(),
+                ));
+            }
+        }
+        // IP literals (including forms like `http://2130706433/`) are never resolved
+        Some(Host::Ipv4(ip)) => check_ip(IpAddr::V4(ip), policy)?,
+        Some(Host::Ipv6(ip)) => check_ip(IpAddr::V6(ip), policy)?,
+    }
+
+    Ok(parsed_url)
+}
+
+fn check_ip(ip: IpAddr, policy: &UrlPolicy) -> Result<()> {
+    match policy.blocked_reason(ip) {
+        Some(reason) => Err(Error::BlockedUrl(format!(
+            "URL points to a restricted {} ({})",
+            reason, ip
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Resolves `host` and returns its addresses, failing if any of them is not allowed
+/// by `policy`.
+///
+/// A host with both public and private addresses is rejected as a whole, since the
+/// connection could end up using any of them.
+pub async fn resolve_public(host: &str, policy: &UrlPolicy) -> Result<Vec<SocketAddr>> {
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, 0))
+        .await
+        .map_err(|e| Error::BlockedUrl(format!("Failed to resolve {}: {}", host, e)))?
+        .collect();
+    if addrs.is_empty() {
+        return Err(Error::BlockedUrl(format!(
+            "{} did not resolve to any address",
+            host
+        )));
+    }
+    for addr in &addrs {
+        if let Some(reason) = policy.blocked_reason(addr.ip()) {
+            return Err(Error::BlockedUrl(format!(
+                "{} resolves to a restricted {} ({})",
+                host,
+                reason,
+                addr.ip()
+            )));
+        }
+    }
+    Ok(addrs)
+}
+
+/// Describes why `ip` must not be fetched, or `None` if it is a public address.
+fn restricted_ip_reason(ip: IpAddr) -> Option<&'static str> {
+    match ip {
+        IpAddr::V4(ip) => restricted_ipv4_reason(ip),
+        IpAddr::V6(ip) => restricted_ipv6_reason(ip),
+    }
+}
+
+/// Ranges that [`UrlPolicy::allow_private_networks`] unlocks.
+fn is_private_network(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_private_ipv4(ip),
+        IpAddr::V6(ip) => match embedded_ipv4(ip) {
+            Some(ipv4) => is_private_ipv4(ipv4),
+            None => (ip.segments()[0] & 0xfe00) == 0xfc00,
+        },
+    }
+}
+
+fn is_private_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    ip.is_private() || (a == 100 && (b & 0xc0) == 64)
+}
+
+fn restricted_ipv4_reason(ip: Ipv4Addr) -> Option<&'static str> {
+    let [a, b, c, _] = ip.octets();
+    if ip.is_loopback() {
+        Some("loopback address")
+    } else if a == 0 {
+        // 0.0.0.0/8 "this network"; 0.0.0.0 reaches the local host on most systems
+        Some("unspecified address")
+    } else if ip.is_private() {
+        Some("private IPv4 address")
+    } else if ip.is_link_local() {
+        // 169.254/16, including cloud metadata endpoints
+        Some("link-local address")
+    } else if a == 100 && (b & 0xc0) == 64 {
+        Some("carrier-grade NAT address")
+    } else if ip.is_multicast() {
+        Some("multicast address")
+    } else if a >= 240 || (a == 192 && b == 0 && c == 0) {
+        // 240/4 (including the broadcast address) and IETF protocol assignments
+        Some("reserved address")
+    } else if ip.is_documentation() {
+        Some("documentation address")
+    } else if a == 198 && (b & 0xfe) == 18 {
+        Some("benchmarking address")
+    } else {
+        None
+    }
+}
+
+fn restricted_ipv6_reason(ip: Ipv6Addr) -> Option<&'static str> {
+    // Addresses that carry an IPv4 address are judged by that address
+    if let Some(ipv4) = embedded_ipv4(ip) {
+        return restricted_ipv4_reason(ipv4);
+    }
+
+    let segments = ip.segments();
+    if ip.is_loopback() {
+        Some("loopback address")
+    } else if ip.is_unspecified() {
+        Some("unspecified address")
+    } else if (segments[0] & 0xfe00) == 0xfc00 {
+        Some("private IPv6 address")
+    } else if (segments[0] & 0xffc0) == 0xfe80 {
+        Some("link-local address")
+    } else if (segments[0] & 0xffc0) == 0xfec0 {
+        Some("site-local IPv6 address")
+    } else if ip.is_multicast() {
+        Some("multicast address")
+    } else if segments[0] == 0x2001 && segments[1] == 0x0db8 {
+        Some("documentation address")
+    } else {
+        None
+    }
+}
+
+/// The IPv4 address inside an IPv4-mapped (`::ffff:a.b.c.d`), IPv4-compatible
+/// (`::a.b.c.d`), NAT64 (`64:ff9b::a.b.c.d`) or 6to4 (`2002:aabb:ccdd::`) address.
+fn embedded_ipv4(ip: Ipv6Addr) -> Option<Ipv4Addr> {
+    let segments = ip.segments();
+    let from_segments =
+        |hi: u16, lo: u16| Ipv4Addr::new((hi >> 8) as u8, hi as u8, (lo >> 8) as u8, lo as u8);
+    match segments {
+        [0, 0, 0, 0, 0, 0xffff, hi, lo] | [0x64, 0xff9b, 0, 0, 0, 0, hi, lo] => {
+            Some(from_segments(hi, lo))
+        }
+        [0, 0, 0, 0, 0, 0, hi, lo] if !ip.is_loopback() && !ip.is_unspecified() => {
+            Some(from_segments(hi, lo))
+        }
+        [0x2002, hi, lo, ..] => Some(from_segments(hi, lo)),
+        _ => None,
+    }
+}
+
+/// DNS resolver for the image fetch client.
+///
+/// Every resolved address is checked before reqwest connects, and the connection
+/// is made to exactly the addresses that passed. A host therefore cannot pass
+/// validation and then resolve to a private address for the actual request (DNS
+/// rebinding). Proxies are exempt, as they usually live on the local network.
+struct PublicResolver {
+    policy: UrlPolicy,
+    proxy_hosts: Vec<String>,
+}
+
+impl PublicResolver {
+    fn new(policy: &UrlPolicy, network: &NetworkConfig) -> Self {
+        let env_proxies = ["ALL_PROXY", "HTTPS_PROXY", "HTTP_PROXY"]
+            .into_iter()
+            .flat_map(|name| [std::env::var(name), std::env::var(name.to_lowercase())])
+            .filter_map(|value| value.ok());
+        let proxy_hosts = std::iter::once(network.proxy.clone())
+            .chain(env_proxies)
+            .filter_map(|proxy| proxy_host(&proxy))
+            .collect();
+        Self {
+            policy: policy.clone(),
+            proxy_hosts,
+        }
+    }
+}
+
+fn proxy_host(proxy: &str) -> Option<String> {
+    let proxy = proxy.trim();
+    if proxy.is_empty() {
+        return None;
+    }
+    // Proxy environment variables are often given without a scheme
+    let url = if proxy.contains("://") {
+        Url::parse(proxy)
+    } else {
+        Url::parse(&format!("http://{}", proxy))
+    };
+    match url.ok()?.host()? {
+        Host::Domain(domain) => Some(domain.to_lowercase()),
+        // IP addresses are not resolved
+        _ => None,
+    }
+}
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_lowercase();
+        let is_proxy = self.proxy_hosts.contains(&host);
+        let policy = self.policy.clone();
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = if is_proxy {
+                tokio::net::lookup_host((host.as_str(), 0)).await?.collect()
+            } else {
+                resolve_public(&host, &policy).await?
+            };
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Surfaces an [`Error::BlockedUrl`] raised by [`PublicResolver`] instead of the
+/// generic connection error reqwest wraps it in.
+fn blocked_or_http(err: reqwest::Error) -> Error {
+    let mut source = std::error::Error::source(&err);
+    while let Some(cause) = source {
+        if let Some(Error::BlockedUrl(message)) = cause.downcast_ref::<Error>() {
+            return Error::BlockedUrl(message.clone());
+        }
+        source = cause.source();
+    }
+    Error::Http(err)
+}
+
+/// How many redirects [`fetch_bytes`] follows before giving up.
+pub const MAX_REDIRECTS: usize = 5;
+
+/// Delay before the first retry of a failed fetch; doubled after every attempt.
+const RETRY_BACKOFF: Duration = Duration::from_millis(500);
+
+/// Resolves the `Location` of a redirect from `current` and validates it like the
+/// original URL.
+fn redirect_target(current: &Url, location: &str, policy: &UrlPolicy) -> Result<Url> {
+    let target = current
+        .join(location)
+        .map_err(|e| Error::InvalidUrl(format!("Invalid redirect location: {}", e)))?;
+    validate_url(target.as_str(), policy)
+}
+
+/// Validates `url` with [`validate_url`] and downloads it, refusing bodies larger
+/// than `policy.max_size` bytes.
+///
+/// Redirects are followed up to [`MAX_REDIRECTS`] hops, validating every hop the
+/// same way as the original URL. Network errors, timeouts and server errors are
+/// retried `policy.retries` times, all within `policy.timeout_secs` in total.
+///
+/// The request goes through the proxy in `network`, if any. The host is resolved
+/// and checked locally first, so a proxy cannot be used to reach hosts that resolve
+/// to private addresses here; direct connections are additionally pinned to the
+/// checked addresses by [`PublicResolver`].
+pub async fn fetch_bytes(url: &str, policy: &UrlPolicy, network: &NetworkConfig) -> Result<Vec<u8>> {
+    let url = validate_url(url, policy)?;
+
+    // Redirects are followed manually in `fetch_once` so that every hop is validated
+    let client = network
+        .client_builder()?
+        .redirect(reqwest::redirect::Policy::none())
+        .dns_resolver(PublicResolver::new(policy, network))
+        .connect_timeout(Duration::from_secs(policy.connect_timeout_secs.max(1)))
+        .build()?;
+
+    with_retries(policy.timeout(), &policy.retry_policy(), url.as_str(), || {
+        fetch_once(&client, url.clone(), policy)
+    })
+    .await
+}
+
+/// Runs `attempt` until it succeeds, fails with a permanent error or runs out of
+/// attempts. Once `deadline` has passed since the start, the running attempt is
+/// cancelled with [`Error::Timeout`]; a retry whose backoff would end after the
+/// deadline is not started.
+async fn with_retries<T, F, Fut>(
+    deadline: Duration,
+    retry: &RetryPolicy,
+    url: &str,
+    mut attempt: F,
+) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let end = tokio::time::Instant::now() + deadline;
+    let mut backoff = retry.initial_backoff;
+    let mut attempts = 1;
+    loop {
+        let result = match tokio::time::timeout_at(end, attempt()).await {
+            Ok(result) => result,
+            Err(_) => Err(Error::Timeout {
+                secs: deadline.as_secs(),
+            }),
+        };
+        match result {
+            Err(e)
+                if attempts < retry.max_attempts
+                    && is_transient(&e)
+                    && tokio::time::Instant::now() + backoff < end =>
+            {
+                eprintln!(
+                    "Fetching {} failed (attempt {}/{}): {}. Retrying in {:?}",
+                    url, attempts, retry.max_attempts, e, backoff
+                );
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+                attempts += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+async fn fetch_once(client: &reqwest::Client, mut url: Url, policy: &UrlPolicy) -> Result<Vec<u8>> {
+    let max_size = policy.max_size;
+    let mut redirects = 0;
+    let mut resp = loop {
+        if let Some(Host::Domain(domain)) = url.host() {
+            resolve_public(domain, policy).await?;
+        }
+        let resp = client
+            .get(url.clone())
+            .send()
+            .await
+            .map_err(blocked_or_http)?;
+        if !matches!(resp.status().as_u16(), 301 | 302 | 303 | 307 | 308) {
+            break resp.error_for_status()?;
+        }
+        if redirects == MAX_REDIRECTS {
+            return Err(Error::InvalidUrl(format!(
+                "Too many redirects (more than {})",
+                MAX_REDIRECTS
+            )));
+        }
+        let location = resp
+            .headers()
+            .get(reqwest::header::LOCATION)
+            .and_then(|v| v.to_str().ok())
+            .ok_or_else(|| Error::InvalidUrl("Redirect without a Location header".to_string()))?;
+        url = redirect_target(&url, location, policy)?;
+        redirects += 1;
+    };
+
+    // Check content length header if available
+    if let Some(content_length) = resp.content_length() {
+        if content_length > max_size {
+            return Err(Error::PayloadTooLarge { limit: max_size });
+        }
+    }
+
+    // Stream download and enforce size limit manually
+    let mut bytes = Vec::new();
+    while let Some(chunk) = resp.chunk().await? {
+        if (bytes.len() + chunk.len()) as u64 > max_size {
+            return Err(Error::PayloadTooLarge { limit: max_size });
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reason(ip: &str) -> Option<&'static str> {
+        restricted_ip_reason(ip.parse().unwrap())
+    }
+
+    fn validate(url: &str) -> Result<Url> {
+        validate_url(url, &UrlPolicy::default())
+    }
+
+    #[test]
+    fn test_validate_url_rejects_scheme_and_local_hosts() {
+        assert!(matches!(validate("not a url"), Err(Error::InvalidUrl(_))));
+        assert!(matches!(validate("file:///etc/passwd"), Err(Error::InvalidUrl(_))));
+        assert!(matches!(validate("http://localhost/a.png"), Err(Error::BlockedUrl(_))));
+        assert!(matches!(validate("http://LocalHost./a.png"), Err(Error::BlockedUrl(_))));
+        assert!(matches!(validate("http://127.0.0.1/a.png"), Err(Error::BlockedUrl(_))));
+        assert!(matches!(validate("http://192.168.1.10/a.png"), Err(Error::BlockedUrl(_))));
+        assert!(matches!(validate("http://[::1]/a.png"), Err(Error::BlockedUrl(_))));
+        assert!(matches!(validate("http://[::ffff:10.0.0.1]/a.png"), Err(Error::BlockedUrl(_))));
+        // Decimal and shortened IPv4 forms are normalized by the URL parser
+        assert!(matches!(validate("http://2130706433/a.png"), Err(Error::BlockedUrl(_))));
+        assert!(matches!(validate("http://127.1/a.png"), Err(Error::BlockedUrl(_))));
+        assert!(validate("http://8.8.8.8/a.png").is_ok());
+        assert!(validate("https://example.com/a.png").is_ok());
+    }
+
+    #[test]
+    fn test_restricted_ipv4_ranges() {
+        assert_eq!(reason("127.0.0.1"), Some("loopback address"));
+        assert_eq!(reason("127.255.255.254"), Some("loopback address"));
+        assert_eq!(reason("0.0.0.0"), Some("unspecified address"));
+        assert_eq!(reason("0.1.2.3"), Some("unspecified address"));
+        assert_eq!(reason("10.0.0.1"), Some("private IPv4 address"));
+        assert_eq!(reason("172.16.0.1"), Some("private IPv4 address"));
+        assert_eq!(reason("172.31.255.255"), Some("private IPv4 address"));
+        assert_eq!(reason("192.168.1.1"), Some("private IPv4 address"));
+        assert_eq!(reason("169.254.169.254"), Some("link-local address"));
+        assert_eq!(reason("100.64.0.1"), Some("carrier-grade NAT address"));
+        assert_eq!(reason("100.127.255.255"), Some("carrier-grade NAT address"));
+        assert_eq!(reason("224.0.0.1"), Some("multicast address"));
+        assert_eq!(reason("240.0.0.1"), Some("reserved address"));
+        assert_eq!(reason("255.255.255.255"), Some("reserved address"));
+        assert_eq!(reason("192.0.0.8"), Some("reserved address"));
+        assert_eq!(reason("192.0.2.1"), Some("documentation address"));
+        assert_eq!(reason("203.0.113.7"), Some("documentation address"));
+        assert_eq!(reason("198.18.0.1"), Some("benchmarking address"));
+        assert_eq!(reason("198.19.255.255"), Some("benchmarking address"));
+    }
+
+    #[test]
+    fn test_public_ipv4_addresses_are_allowed() {
+        for ip in [
+            "8.8.8.8",
+            "1.1.1.1",
+            "100.63.255.255",
+            "100.128.0.1",
+            "172.32.0.1",
+            "198.20.0.1",
+            "223.255.255.255",
+        ] {
+            assert_eq!(reason(ip), None, "{}", ip);
+        }
+    }
+
+    #[test]
+    fn test_restricted_ipv6_ranges() {
+        assert_eq!(reason("::1"), Some("loopback address"));
+        assert_eq!(reason("::"), Some("unspecified address"));
+        assert_eq!(reason("fc00::1"), Some("private IPv6 address"));
+        assert_eq!(reason("fd12:3456::1"), Some("private IPv6 address"));
+        assert_eq!(reason("fe80::1"), Some("link-local address"));
+        assert_eq!(reason("febf::1"), Some("link-local address"));
+        assert_eq!(reason("fec0::1"), Some("site-local IPv6 address"));
+        assert_eq!(reason("ff02::1"), Some("multicast address"));
+        assert_eq!(reason("2001:db8::1"), Some("documentation address"));
+        assert_eq!(reason("2001:4860:4860::8888"), None);
+        assert_eq!(reason("2606:4700::1111"), None);
+    }
+
+    #[test]
+    fn test_ipv6_with_embedded_ipv4() {
+        // IPv4-mapped
+        assert_eq!(reason("::ffff:127.0.0.1"), Some("loopback address"));
+        assert_eq!(reason("::ffff:169.254.169.254"), Some("link-local address"));
+        assert_eq!(reason("::ffff:100.64.0.1"), Some("carrier-grade NAT address"));
+        assert_eq!(reason("::ffff:8.8.8.8"), None);
+        // IPv4-compatible
+        assert_eq!(reason("::10.0.0.1"), Some("private IPv4 address"));
+        // NAT64
+        assert_eq!(reason("64:ff9b::192.168.0.1"), Some("private IPv4 address"));
+        assert_eq!(reason("64:ff9b::8.8.8.8"), None);
+        // 6to4 (2002:7f00:1:: carries 127.0.0.1)
+        assert_eq!(reason("2002:7f00:1::"), Some("loopback address"));
+        assert_eq!(reason("2002:808:808::"), None);
+    }
+
+    #[test]
+    fn test_redirect_target() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let policy = UrlPolicy::default();
+        let current = Url::parse("https://images.example.com/a/b.png")?;
+        assert_eq!(
+            redirect_target(&current, "https://cdn.example.net/x.png", &policy)?.as_str(),
+            "https://cdn.example.net/x.png"
+        );
+        assert_eq!(
+            redirect_target(&current, "/c.png", &policy)?.as_str(),
+            "https://images.example.com/c.png"
+        );
+        assert_eq!(
+            redirect_target(&current, "c.png?size=2", &policy)?.as_str(),
+            "https://images.example.com/a/c.png?size=2"
+        );
+
+        // Every hop is held to the same rules as the original URL
+        for location in [
+            "http://127.0.0.1/admin",
+            "http://[::ffff:169.254.169.254]/latest/meta-data",
+            "http://localhost:8080/",
+            "//10.0.0.1/x.png",
+        ] {
+            assert!(matches!(
+                redirect_target(&current, location, &policy),
+                Err(Error::BlockedUrl(_))
+            ));
+        }
+        assert!(matches!(
+            redirect_target(&current, "file:///etc/passwd", &policy),
+            Err(Error::InvalidUrl(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_host_matches() {
+        assert!(host_matches("example.com", "example.com"));
+        assert!(host_matches("img.Example.com.", "example.com"));
+        assert!(host_matches("a.b.example.com", "*.example.com"));
+        assert!(host_matches("a.example.com", ".example.com"));
+        assert!(!host_matches("badexample.com", "example.com"));
+        assert!(!host_matches("example.com.evil", "example.com"));
+        assert!(!host_matches("example.com", ""));
+    }
+
+    #[test]
+    fn test_url_policy_domain_lists() {
+        let policy = UrlPolicy {
+            allow_domains: vec!["example.com".to_string(), "8.8.8.8".to_string()],
+            deny_domains: vec!["private.example.com".to_string()],
+            ..Default::default()
+        };
+        assert!(validate_url("https://cdn.example.com/a.png", &policy).is_ok());
+        assert!(validate_url("http://8.8.8.8/a.png", &policy).is_ok());
+        assert!(matches!(
+            validate_url("https://other.org/a.png", &policy),
+            Err(Error::BlockedUrl(_))
+        ));
+        assert!(matches!(
+            validate_url("https://x.private.example.com/a.png", &policy),
+            Err(Error::BlockedUrl(_))
+        ));
+
+        // Allowing a domain does not unlock restricted addresses
+        let policy = UrlPolicy {
+            allow_domains: vec!["127.0.0.1".to_string()],
+            ..Default::default()
+        };
+        assert!(matches!(
+            validate_url("http://127.0.0.1/a.png", &policy),
+            Err(Error::BlockedUrl(_))
+        ));
+    }
+
+    #[test]
+    fn test_url_policy_private_networks() {
+        let lan = UrlPolicy {
+            allow_private_networks: true,
+            ..Default::default()
+        };
+        for url in [
+            "http://192.168.1.10/a.png",
+            "http://10.1.2.3/a.png",
+            "http://172.20.0.5/a.png",
+            "http://100.100.1.1/a.png",
+            "http://[fd00::10]/a.png",
+            "http://[::ffff:192.168.1.10]/a.png",
+        ] {
+            assert!(validate(url).is_err(), "{}", url);
+            assert!(validate_url(url, &lan).is_ok(), "{}", url);
+        }
+
+        // Loopback, link-local and metadata endpoints stay blocked
+        for url in [
+            "http://127.0.0.1/a.png",
+            "http://[::1]/a.png",
+            "http://169.254.169.254/latest/meta-data",
+            "http://[fe80::1]/a.png",
+            "http://0.0.0.0/a.png",
+        ] {
+            assert!(validate_url(url, &lan).is_err(), "{}", url);
+        }
+    }
+
+    #[test]
+    fn test_proxy_host() {
+        assert_eq!(proxy_host("http://Proxy.corp:3128"), Some("proxy.corp".to_string()));
+        assert_eq!(proxy_host("proxy.corp:3128"), Some("proxy.corp".to_string()));
+        assert_eq!(proxy_host("socks5h://proxy.corp"), Some("proxy.corp".to_string()));
+        assert_eq!(proxy_host("http://10.0.0.1:3128"), None);
+        assert_eq!(proxy_host(""), None);
+    }
+
+    #[tokio::test]
+    async fn test_resolver_blocks_connection_to_restricted_address(
+    ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let resolver = PublicResolver {
+            policy: UrlPolicy::default(),
+            proxy_hosts: Vec::new(),
+        };
+        assert!(resolver.resolve("localhost".parse()?).await.is_err());
+
+        // The error raised while connecting is reported as a blocked URL
+        let client = reqwest::Client::builder()
+            .no_proxy()
+            .dns_resolver(resolver)
+            .build()?;
+        let err = client.get("http://localhost:9/").send().await.unwrap_err();
+        assert!(matches!(blocked_or_http(err), Error::BlockedUrl(_)));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_resolver_allows_proxy_host() -> std::result::Result<(), Box<dyn std::error::Error>>
+    {
+        let network = NetworkConfig {
+            proxy: "http://localhost:3128".to_string(),
+            ..Default::default()
+        };
+        let resolver = PublicResolver::new(&UrlPolicy::default(), &network);
+        let addrs = resolver
+            .resolve("localhost".parse()?)
+            .await
+            .map_err(|e| e.to_string())?;
+        assert!(addrs.count() > 0);
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_with_retries_gives_up_on_hung_request() {
+        let retry = UrlPolicy::default().retry_policy();
+        let started = tokio::time::Instant::now();
+        let mut calls = 0;
+        let result: Result<()> = with_retries(Duration::from_secs(5), &retry, "test", || {
+            calls += 1;
+            std::future::pending()
+        })
+        .await;
+        assert!(matches!(result, Err(Error::Timeout { secs: 5 })));
+        assert_eq!(calls, 1);
+        assert_eq!(started.elapsed(), Duration::from_secs(5));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_with_retries_deadline_covers_all_attempts() {
+        let retry = UrlPolicy::default().retry_policy();
+        let started = tokio::time::Instant::now();
+        let mut calls = 0;
+        let result: Result<()> = with_retries(Duration::from_secs(5), &retry, "test", || {
+            calls += 1;
+            let attempt = calls;
+            async move {
+                if attempt == 1 {
+                    // Fails after most of the time is used up; the retry gets the rest
+                    tokio::time::sleep(Duration::from_secs(3)).await;
+                    Err(Error::IncompleteDownload {
+                        expected: 10,
+                        received: 5,
+                    })
+                } else {
+                    std::future::pending().await
+                }
+            }
+        })
+        .await;
+        assert!(matches!(result, Err(Error::Timeout { secs: 5 })));
+        assert_eq!(calls, 2);
+        assert_eq!(started.elapsed(), Duration::from_secs(5));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_with_retries_retries_transient_errors_only() {
+        let retry = UrlPolicy::default().retry_policy();
+
+        let mut calls = 0;
+        let result = with_retries(Duration::from_secs(5), &retry, "test", || {
+            calls += 1;
+            let attempt = calls;
+            async move {
+                if attempt < 3 {
+                    Err(Error::IncompleteDownload {
+                        expected: 10,
+                        received: 5,
+                    })
+                } else {
+                    Ok(attempt)
+                }
+            }
+        })
+        .await;
+        assert_eq!(result.unwrap(), 3);
+
+        let mut calls = 0;
+        let result: Result<()> = with_retries(Duration::from_secs(5), &retry, "test", || {
+            calls += 1;
+            async { Err(Error::PayloadTooLarge { limit: 1 }) }
+        })
+        .await;
+        assert!(matches!(result, Err(Error::PayloadTooLarge { .. })));
+        assert_eq!(calls, 1);
+    }
+}
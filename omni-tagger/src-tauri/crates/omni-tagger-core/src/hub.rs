@@ -0,0 +1,195 @@
+use crate::catalog::ModelEntry;
+use crate::error::{Error, Result};
+use crate::tagger::PreprocessConfig;
+use url::Url;
+
+pub const DEFAULT_BASE_URL: &str = "https://huggingface.co";
+pub const DEFAULT_REVISION: &str = "main";
+
+/// Files every WD tagger repository provides.
+pub const MODEL_FILE: &str = "model.onnx";
+pub const TAGS_FILE: &str = "selected_tags.csv";
+
+/// Checks that `repo_id` has the `owner/name` form used by Hugging Face.
+fn split_repo_id(repo_id: &str) -> Result<(&str, &str)> {
+    let valid_part = |part: &str| {
+        !part.is_empty()
+            && part != "."
+            && part != ".."
+            && part
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    };
+    match repo_id.split_once('/') {
+        Some((owner, name)) if valid_part(owner) && valid_part(name) => Ok((owner, name)),
+        _ => Err(Error::InvalidRepo(format!(
+            "{:?} is not a repository id of the form owner/name",
+            repo_id
+        ))),
+    }
+}
+
+fn check_revision(revision: &str) -> Result<()> {
+    let valid = !revision.is_empty()
+        && !revision
+            .split('/')
+            .any(|part| part.is_empty() || part == "..")
+        && revision
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '/'));
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::InvalidRepo(format!(
+            "Invalid revision {:?}",
+            revision
+        )))
+    }
+}
+
+/// Download URL of `file` in `repo_id` at `revision`, e.g.
+/// `https://huggingface.co/SmilingWolf/wd-vit-tagger-v3/resolve/main/model.onnx`.
+///
+/// `base_url` may point at a mirror; its path is kept as a prefix.
+pub fn file_url(base_url: &str, repo_id: &str, revision: &str, file: &str) -> Result<String> {
+    let (owner, name) = split_repo_id(repo_id)?;
+    check_revision(revision)?;
+    let mut url =
+        Url::parse(base_url).map_err(|e| Error::InvalidUrl(format!("{}: {}", base_url, e)))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(Error::InvalidUrl(format!(
+            "Unsupported scheme for model hub: {}",
+            url.scheme()
+        )));
+    }
+    url.path_segments_mut()
+        .map_err(|_| Error::InvalidUrl(base_url.to_string()))?
+        .pop_if_empty()
+        // Revisions such as `refs/pr/1` are a single, escaped path segment
+        .extend([owner, name, "resolve", revision, file]);
+    Ok(url.into())
+}
+
+/// Catalog entry for a tagger hosted on the hub. Its files are stored in a folder
+/// of their own under `models/`, so several revisions can be installed side by side.
+pub fn model_entry(base_url: &str, repo_id: &str, revision: &str) -> Result<ModelEntry> {
+    let (owner, name) = split_repo_id(repo_id)?;
+    check_revision(revision)?;
+    let (id, folder) = if revision == DEFAULT_REVISION {
+        (repo_id.to_string(), format!("models/{}--{}", owner, name))
+    } else {
+        (
+            format!("{}@{}", repo_id, revision),
+            format!("models/{}--{}--{}", owner, name, revision.replace('/', "-")),
+        )
+    };
+    Ok(ModelEntry {
+        id,
+        name: name.to_string(),
+        model_url: file_url(base_url, repo_id, revision, MODEL_FILE)?,
+        tags_url: file_url(base_url, repo_id, revision, TAGS_FILE)?,
+        model_path: format!("{}/{}", folder, MODEL_FILE),
+        tags_path: format!("{}/{}", folder, TAGS_FILE),
+        sha256: None,
+        tags_sha256: None,
+        size: None,
+        // All WD taggers take 448px BGR input without normalization
+        preprocessing: PreprocessConfig::default(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_url() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        assert_eq!(
+            file_url(
+                DEFAULT_BASE_URL,
+                "SmilingWolf/wd-vit-tagger-v3",
+                "main",
+                MODEL_FILE
+            )?,
+            "https://huggingface.co/SmilingWolf/wd-vit-tagger-v3/resolve/main/model.onnx"
+        );
+        assert_eq!(
+            file_url("http://127.0.0.1:8080/hf/", "a/b", "refs/pr/1", TAGS_FILE)?,
+            "http://127.0.0.1:8080/hf/a/b/resolve/refs%2Fpr%2F1/selected_tags.csv"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_invalid_input() {
+        for repo in [
+            "",
+            "wd-vit-tagger-v3",
+            "a/b/c",
+            "../b",
+            "a/..",
+            "a/b?x=1",
+            "a /b",
+        ] {
+            assert!(
+                matches!(
+                    file_url(DEFAULT_BASE_URL, repo, "main", MODEL_FILE),
+                    Err(Error::InvalidRepo(_))
+                ),
+                "{:?} should be rejected",
+                repo
+            );
+        }
+        for revision in ["", "../main", "refs//pr", "main?x"] {
+            assert!(matches!(
+                file_url(DEFAULT_BASE_URL, "a/b", revision, MODEL_FILE),
+                Err(Error::InvalidRepo(_))
+            ));
+        }
+        assert!(matches!(
+            file_url("file:///etc", "a/b", "main", MODEL_FILE),
+            Err(Error::InvalidUrl(_))
+        ));
+    }
+
+    #[test]
+    fn test_model_entry() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let entry = model_entry(DEFAULT_BASE_URL, "SmilingWolf/wd-vit-tagger-v3", "main")?;
+        assert_eq!(entry.id, "SmilingWolf/wd-vit-tagger-v3");
+        assert_eq!(
+            entry.model_path,
+            "models/SmilingWolf--wd-vit-tagger-v3/model.onnx"
+        );
+        assert_eq!(
+            entry.tags_path,
+            "models/SmilingWolf--wd-vit-tagger-v3/selected_tags.csv"
+        );
+
+        let entry = model_entry(DEFAULT_BASE_URL, "a/b", "v2.0")?;
+        assert_eq!(entry.id, "a/b@v2.0");
+        assert_eq!(entry.model_path, "models/a--b--v2.0/model.onnx");
+        Ok(())
+    }
+    #[test]
+    fn test_hub_files_are_not_builtin_models() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        use crate::catalog::Catalog;
+        use std::path::Path;
+
+        // Hub files share their names with the built-in SwinV2 model; a missing one
+        // must not be replaced by a download of that model
+        let entry = model_entry(DEFAULT_BASE_URL, "owner/repo", "main")?;
+        let model = Path::new("/data/com.omnitagger.app").join(&entry.model_path);
+        let tags = Path::new("/data/com.omnitagger.app").join(&entry.tags_path);
+        let builtin = Catalog::builtin();
+        assert!(builtin.find_by_model_path(&model).is_none());
+        assert!(builtin.find_by_tags_path(&tags).is_none());
+
+        let mut catalog = Catalog::builtin();
+        catalog.merge(Catalog {
+            models: vec![entry],
+        });
+        let found = catalog.find_by_model_path(&model).ok_or("hub model not found")?;
+        assert_eq!(found.id, "owner/repo");
+        Ok(())
+    }
+}
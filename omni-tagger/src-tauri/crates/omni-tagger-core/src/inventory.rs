@@ -0,0 +1,338 @@
+use crate::catalog::Catalog;
+use crate::checksum::{checksum_path, verify_file, ChecksumStatus};
+use crate::download::part_path;
+use crate::error::{Error, Result};
+use crate::tagger::model_input_shape;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// File in the models directory that remembers when each model was last used.
+const USAGE_FILE: &str = "usage.json";
+
+/// Uses within this many seconds of the recorded one are not written again, so
+/// tagging a batch of images does not rewrite the file for every image.
+const USAGE_RESOLUTION_SECS: u64 = 60;
+
+/// Details about an installed model, as shown by the model manager.
+#[derive(Debug, Clone, Serialize)]
+pub struct ModelInfo {
+    pub path: PathBuf,
+    /// Size of the model file in bytes.
+    pub size: u64,
+    pub checksum: ChecksumStatus,
+    pub tags_path: Option<PathBuf>,
+    pub tag_count: Option<usize>,
+    pub input_shape: Option<Vec<i64>>,
+    /// Unix timestamp (seconds) of the last time the model was used for tagging.
+    pub last_used: Option<u64>,
+}
+
+/// All `.onnx` files under `models_dir`, including per-model folders, sorted by path.
+pub fn find_models(models_dir: &Path) -> Vec<PathBuf> {
+    let mut models = Vec::new();
+    let mut dirs = vec![models_dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                // Skip bundles that are still being imported
+                if !entry.file_name().to_string_lossy().starts_with('.') {
+                    dirs.push(path);
+                }
+            } else if path.extension().and_then(|e| e.to_str()) == Some("onnx") {
+                models.push(path);
+            }
+        }
+    }
+    models.sort();
+    models
+}
+
+/// The tags file that belongs to `model`: `selected_tags.csv` in a per-model
+/// folder, `<model name>.csv`, or the shared `tags.csv` next to it.
+pub fn tags_for_model(model: &Path) -> Option<PathBuf> {
+    let dir = model.parent()?;
+    let stem = model.file_stem()?.to_str()?;
+    [
+        dir.join("selected_tags.csv"),
+        dir.join(format!("{}.csv", stem)),
+        dir.join("tags.csv"),
+    ]
+    .into_iter()
+    .find(|p| p.exists())
+}
+
+/// Number of tags in a `selected_tags.csv` style file (excluding the header).
+pub fn count_tags(tags_path: &Path) -> Result<usize> {
+    let mut reader = csv::Reader::from_path(tags_path).map_err(|e| Error::TagsFile {
+        path: tags_path.to_path_buf(),
+        message: e.to_string(),
+    })?;
+    Ok(reader.records().filter(|r| r.is_ok()).count())
+}
+
+/// Hashes `file` and compares it with the checksum `catalog` pins for it or, if there
+/// is none, the one recorded next to it, so a file replaced together with its
+/// recorded checksum is still caught.
+pub fn checksum_status(file: &Path, catalog: &Catalog) -> Result<ChecksumStatus> {
+    verify_file(file, catalog.pinned_checksum(file))
+}
+
+/// Collects size, checksum status (see [`checksum_status`]), tag count, input shape
+/// and last use of `model`.
+///
+/// This hashes the file and opens it with ONNX Runtime, so it takes a few seconds
+/// for large models. A model that cannot be opened is still reported, without an
+/// input shape.
+pub fn inspect(
+    models_dir: &Path,
+    model: &Path,
+    tags_path: Option<&Path>,
+    catalog: &Catalog,
+) -> Result<ModelInfo> {
+    let size = std::fs::metadata(model)
+        .map_err(|e| Error::io(format!("Failed to read {:?}", model), e))?
+        .len();
+    let tags_path = tags_path
+        .map(Path::to_path_buf)
+        .or_else(|| tags_for_model(model));
+    let tag_count = tags_path.as_deref().and_then(|p| count_tags(p).ok());
+    let input_shape = match model_input_shape(model) {
+        Ok(shape) => shape,
+        Err(e) => {
+            eprintln!("Failed to read input shape of {:?}: {}", model, e);
+            None
+        }
+    };
+    Ok(ModelInfo {
+        path: model.to_path_buf(),
+        size,
+        checksum: checksum_status(model, catalog)?,
+        tags_path,
+        tag_count,
+        input_shape,
+        last_used: last_used(models_dir, model),
+    })
+}
+
+/// Total size of everything under `models_dir`, including tags files, checksums
+/// and unfinished `.part` downloads.
+pub fn disk_usage(models_dir: &Path) -> u64 {
+    let mut total = 0;
+    let mut dirs = vec![models_dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            match entry.metadata() {
+                Ok(meta) if meta.is_dir() => dirs.push(entry.path()),
+                Ok(meta) => total += meta.len(),
+                Err(_) => {}
+            }
+        }
+    }
+    total
+}
+
+fn read_usage(models_dir: &Path) -> BTreeMap<String, u64> {
+    std::fs::read_to_string(models_dir.join(USAGE_FILE))
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn write_usage(models_dir: &Path, usage: &BTreeMap<String, u64>) -> Result<()> {
+    let path = models_dir.join(USAGE_FILE);
+    let content = serde_json::to_string_pretty(usage).map_err(|e| {
+        Error::io(
+            format!("Failed to serialize {:?}", path),
+            std::io::Error::other(e),
+        )
+    })?;
+    std::fs::write(&path, content).map_err(|e| Error::io(format!("Failed to write {:?}", path), e))
+}
+
+fn usage_key(model: &Path) -> String {
+    model.display().to_string()
+}
+
+pub fn last_used(models_dir: &Path, model: &Path) -> Option<u64> {
+    read_usage(models_dir).get(&usage_key(model)).copied()
+}
+
+/// Remembers that `model` was just used for tagging.
+pub fn record_use(models_dir: &Path, model: &Path) -> Result<()> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let mut usage = read_usage(models_dir);
+    let previous = usage.insert(usage_key(model), now);
+    if previous.is_some_and(|t| now.saturating_sub(t) < USAGE_RESOLUTION_SECS) {
+        return Ok(());
+    }
+    std::fs::create_dir_all(models_dir)
+        .map_err(|e| Error::io(format!("Failed to create {:?}", models_dir), e))?;
+    write_usage(models_dir, &usage)
+}
+
+/// Deletes `model` together with its checksum and any unfinished download, and
+/// returns the number of bytes freed.
+///
+/// Models in a folder of their own (downloaded from the hub or imported) take the
+/// folder with them once no other model is left in it; shared tags files directly
+/// in `models_dir` are kept. Paths outside `models_dir` are refused.
+pub fn delete_model(models_dir: &Path, model: &Path) -> Result<u64> {
+    let canonical_dir = models_dir
+        .canonicalize()
+        .map_err(|e| Error::io(format!("Failed to resolve {:?}", models_dir), e))?;
+    let canonical = model
+        .canonicalize()
+        .map_err(|e| Error::io(format!("Failed to resolve {:?}", model), e))?;
+    if !canonical.starts_with(&canonical_dir)
+        || canonical.extension().and_then(|e| e.to_str()) != Some("onnx")
+    {
+        return Err(Error::io(
+            format!("Refusing to delete {:?}", model),
+            std::io::Error::from(std::io::ErrorKind::PermissionDenied),
+        ));
+    }
+
+    let mut freed = 0;
+    for path in [
+        canonical.clone(),
+        checksum_path(&canonical),
+        part_path(&canonical),
+    ] {
+        if let Ok(meta) = std::fs::metadata(&path) {
+            std::fs::remove_file(&path)
+                .map_err(|e| Error::io(format!("Failed to delete {:?}", path), e))?;
+            freed += meta.len();
+        }
+    }
+
+    if let Some(folder) = canonical.parent().filter(|p| *p != canonical_dir) {
+        if find_models(folder).is_empty() {
+            let size = disk_usage(folder);
+            std::fs::remove_dir_all(folder)
+                .map_err(|e| Error::io(format!("Failed to delete {:?}", folder), e))?;
+            freed += size;
+        }
+    }
+
+    let mut usage = read_usage(models_dir);
+    if usage.remove(&usage_key(model)).is_some() {
+        write_usage(models_dir, &usage)?;
+    }
+    Ok(freed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn models_dir(name: &str) -> std::result::Result<PathBuf, Box<dyn std::error::Error>> {
+        let dir = std::env::temp_dir().join(name);
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("models/owner--repo"))?;
+        let dir = dir.join("models");
+        std::fs::write(dir.join("model.onnx"), b"model")?;
+        std::fs::write(dir.join("tags.csv"), "tag_id,name\n0,general\n1,1girl\n")?;
+        std::fs::write(dir.join("owner--repo/model.onnx"), b"hub model")?;
+        std::fs::write(
+            dir.join("owner--repo/selected_tags.csv"),
+            "tag_id,name\n0,general\n",
+        )?;
+        Ok(dir)
+    }
+
+    #[test]
+    fn test_find_models_and_tags() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let dir = models_dir("omni_tagger_test_inventory_find")?;
+        let models = find_models(&dir);
+        assert_eq!(
+            models,
+            vec![dir.join("model.onnx"), dir.join("owner--repo/model.onnx")]
+        );
+        assert_eq!(tags_for_model(&models[0]), Some(dir.join("tags.csv")));
+        assert_eq!(
+            tags_for_model(&models[1]),
+            Some(dir.join("owner--repo/selected_tags.csv"))
+        );
+        assert_eq!(count_tags(&dir.join("tags.csv"))?, 2);
+        assert_eq!(disk_usage(&dir), 5 + 30 + 9 + 22);
+
+        std::fs::remove_dir_all(dir.parent().ok_or("no parent")?)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_use() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let dir = models_dir("omni_tagger_test_inventory_usage")?;
+        let model = dir.join("model.onnx");
+        assert_eq!(last_used(&dir, &model), None);
+        record_use(&dir, &model)?;
+        assert!(last_used(&dir, &model).is_some());
+
+        std::fs::remove_dir_all(dir.parent().ok_or("no parent")?)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_checksum_status_prefers_pinned() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let dir = models_dir("omni_tagger_test_inventory_checksum")?;
+        let model = dir.join("model.onnx");
+        // The file was replaced together with its recorded checksum
+        crate::checksum::record_checksum(&model, &crate::checksum::sha256_file(&model)?)?;
+
+        let mut catalog = Catalog::builtin();
+        assert!(matches!(
+            checksum_status(&model, &catalog)?,
+            ChecksumStatus::Ok { .. }
+        ));
+
+        let pinned = "0".repeat(64);
+        for entry in catalog.models.iter_mut() {
+            entry.sha256 = Some(pinned.clone());
+        }
+        assert!(matches!(
+            checksum_status(&model, &catalog)?,
+            ChecksumStatus::Mismatch { expected, .. } if expected == pinned
+        ));
+
+        std::fs::remove_dir_all(dir.parent().ok_or("no parent")?)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_delete_model() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let dir = models_dir("omni_tagger_test_inventory_delete")?;
+
+        // A model in its own folder takes the folder with it
+        let freed = delete_model(&dir, &dir.join("owner--repo/model.onnx"))?;
+        assert_eq!(freed, 9 + 22);
+        assert!(!dir.join("owner--repo").exists());
+
+        // The shared tags file stays
+        std::fs::write(checksum_path(&dir.join("model.onnx")), b"hash")?;
+        assert_eq!(delete_model(&dir, &dir.join("model.onnx"))?, 5 + 4);
+        assert!(dir.join("tags.csv").exists());
+
+        // Only models inside the models directory can be deleted
+        assert!(delete_model(&dir, &dir.join("tags.csv")).is_err());
+        let outside = dir.parent().ok_or("no parent")?.join("outside.onnx");
+        std::fs::write(&outside, b"x")?;
+        assert!(delete_model(&dir, &outside).is_err());
+        assert!(delete_model(&dir, &dir.join("../outside.onnx")).is_err());
+        assert!(outside.exists());
+
+        std::fs::remove_dir_all(dir.parent().ok_or("no parent")?)?;
+        Ok(())
+    }
+}
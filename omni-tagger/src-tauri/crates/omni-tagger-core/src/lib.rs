@@ -0,0 +1,14 @@
+pub mod bundle;
+pub mod catalog;
+pub mod checksum;
+pub mod decode;
+pub mod download;
+mod error;
+pub mod fetch;
+pub mod hub;
+pub mod inventory;
+pub mod network;
+mod tagger;
+
+pub use error::{Error, Result};
+pub use tagger::{postprocess_tags, preprocess, PixelFormat, PreprocessConfig, Tagger};
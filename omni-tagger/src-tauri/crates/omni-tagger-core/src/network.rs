@@ -0,0 +1,109 @@
+use crate::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Proxy and TLS settings applied to every HTTP client: model downloads, checksum
+/// lookups and image URL fetching.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct NetworkConfig {
+    /// Proxy for all requests, e.g. `http://proxy:3128` or `socks5h://proxy:1080`.
+    /// When empty, the `HTTPS_PROXY`/`HTTP_PROXY`/`NO_PROXY` environment variables apply.
+    pub proxy: String,
+    /// Hosts, domains (`.corp.example`) or IP ranges (`10.0.0.0/8`) that bypass `proxy`.
+    pub no_proxy: Vec<String>,
+    /// PEM file with extra root certificates, trusted in addition to the system ones.
+    pub ca_bundle: String,
+}
+
+impl NetworkConfig {
+    /// A client builder with the proxy and extra certificates applied, for callers
+    /// that need further options such as a redirect policy.
+    pub fn client_builder(&self) -> Result<reqwest::ClientBuilder> {
+        let mut builder = reqwest::Client::builder();
+
+        let proxy = self.proxy.trim();
+        if !proxy.is_empty() {
+            let no_proxy = reqwest::NoProxy::from_string(&self.no_proxy.join(","));
+            let proxy = reqwest::Proxy::all(proxy)
+                .map_err(|e| Error::Network(format!("invalid proxy {:?}: {}", proxy, e)))?
+                .no_proxy(no_proxy);
+            builder = builder.proxy(proxy);
+        }
+
+        let ca_bundle = self.ca_bundle.trim();
+        if !ca_bundle.is_empty() {
+            builder = builder.tls_certs_merge(load_certificates(Path::new(ca_bundle))?);
+        }
+
+        Ok(builder)
+    }
+
+    pub fn client(&self) -> Result<reqwest::Client> {
+        Ok(self.client_builder()?.build()?)
+    }
+}
+
+fn load_certificates(path: &Path) -> Result<Vec<reqwest::Certificate>> {
+    let pem = std::fs::read(path)
+        .map_err(|e| Error::io(format!("Failed to read CA bundle {:?}", path), e))?;
+    let certs = reqwest::Certificate::from_pem_bundle(&pem)
+        .map_err(|e| Error::Network(format!("invalid CA bundle {:?}: {}", path, e)))?;
+    if certs.is_empty() {
+        return Err(Error::Network(format!(
+            "CA bundle {:?} contains no certificates",
+            path
+        )));
+    }
+    Ok(certs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_with_proxy() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        NetworkConfig::default().client()?;
+
+        for proxy in [
+            "http://proxy.example:3128",
+            "https://proxy.example",
+            "socks5h://proxy.example:1080",
+        ] {
+            let config = NetworkConfig {
+                proxy: proxy.to_string(),
+                no_proxy: vec!["localhost".to_string(), ".corp.example".to_string()],
+                ..Default::default()
+            };
+            config.client()?;
+        }
+
+        let config = NetworkConfig {
+            proxy: "not a proxy".to_string(),
+            ..Default::default()
+        };
+        assert!(matches!(config.client(), Err(Error::Network(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_client_with_invalid_ca_bundle() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let missing = NetworkConfig {
+            ca_bundle: "/nonexistent/omni_tagger_ca.pem".to_string(),
+            ..Default::default()
+        };
+        assert!(matches!(missing.client(), Err(Error::Io { .. })));
+
+        let path = std::env::temp_dir().join("omni_tagger_test_ca_bundle.pem");
+        std::fs::write(&path, "not a certificate")?;
+        let empty = NetworkConfig {
+            ca_bundle: path.display().to_string(),
+            ..Default::default()
+        };
+        assert!(matches!(empty.client(), Err(Error::Network(_))));
+
+        std::fs::remove_file(path)?;
+        Ok(())
+    }
+}
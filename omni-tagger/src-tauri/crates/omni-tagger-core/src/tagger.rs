@@ -1,9 +1,38 @@
-use crate::config::PreprocessConfig;
-use anyhow::{Context, Result};
+use crate::error::{Error, Result};
 use image::{DynamicImage, GenericImageView};
 use ndarray::Array4;
 use ort::session::{builder::GraphOptimizationLevel, Session};
+use serde::{Deserialize, Serialize};
 use std::fs::File;
+use std::path::Path;
+
+/// Channel order the model expects; WD14 and WD v3 models use BGR.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum PixelFormat {
+    #[default]
+    #[serde(alias = "BGR")]
+    Bgr,
+    #[serde(alias = "RGB")]
+    Rgb,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PreprocessConfig {
+    pub input_size: u32,
+    pub format: PixelFormat,
+    pub normalize: bool,
+}
+
+impl Default for PreprocessConfig {
+    fn default() -> Self {
+        Self {
+            input_size: 448,
+            format: PixelFormat::Bgr,
+            normalize: false,
+        }
+    }
+}
 
 pub struct Tagger {
     session: Session,
@@ -12,16 +41,31 @@ pub struct Tagger {
 }
 
 impl Tagger {
-    pub fn new(model_path: &str, tags_csv_path: &str, config: PreprocessConfig) -> Result<Self> {
+    pub fn new(
+        model_path: impl AsRef<Path>,
+        tags_csv_path: impl AsRef<Path>,
+        config: PreprocessConfig,
+    ) -> Result<Self> {
+        let model_path = model_path.as_ref();
+        let tags_csv_path = tags_csv_path.as_ref();
+        let tags_error = |message: String| Error::TagsFile {
+            path: tags_csv_path.to_path_buf(),
+            message,
+        };
+        let model_error = |message: String| Error::ModelLoad {
+            path: model_path.to_path_buf(),
+            message,
+        };
+
         // Load tags
-        let file = File::open(tags_csv_path).context("Failed to open tags file")?;
+        let file = File::open(tags_csv_path).map_err(|e| tags_error(e.to_string()))?;
         let mut rdr = csv::ReaderBuilder::new()
             .has_headers(false)
             .from_reader(file);
 
         let mut tags = Vec::new();
         for result in rdr.records() {
-            let record = result.context("Failed to read CSV record")?;
+            let record = result.map_err(|e| tags_error(e.to_string()))?;
             if let Some(tag) = record.get(1) {
                 tags.push(tag.to_string());
             }
@@ -29,13 +73,13 @@ impl Tagger {
 
         // Initialize ORT session
         let session = Session::builder()
-            .map_err(|e| anyhow::anyhow!("Failed to build session: {:?}", e))?
+            .map_err(|e| model_error(format!("Failed to build session: {:?}", e)))?
             .with_optimization_level(GraphOptimizationLevel::Level3)
-            .map_err(|e| anyhow::anyhow!("Failed to set optimization level: {:?}", e))?
+            .map_err(|e| model_error(format!("Failed to set optimization level: {:?}", e)))?
             .with_intra_threads(4)
-            .map_err(|e| anyhow::anyhow!("Failed to set intra threads: {:?}", e))?
+            .map_err(|e| model_error(format!("Failed to set intra threads: {:?}", e)))?
             .commit_from_file(model_path)
-            .context("Failed to load model")?;
+            .map_err(|e| model_error(e.to_string()))?;
 
         Ok(Self {
             session,
@@ -44,16 +88,27 @@ impl Tagger {
         })
     }
 
+    /// Shape of the model's first input, e.g. `[-1, 448, 448, 3]` (`-1` is dynamic).
+    pub fn input_shape(&self) -> Option<Vec<i64>> {
+        input_shape_of(&self.session)
+    }
+
     pub fn infer(&mut self, image: &DynamicImage, threshold: f32) -> Result<Vec<(String, f32)>> {
         let input_tensor = preprocess(image, &self.config);
 
         // Run inference
         // Explicitly create Value from ndarray
-        let input_value = ort::value::Value::from_array(input_tensor)?;
-        let outputs = self.session.run(ort::inputs!["input_1" => input_value])?;
+        let inference_error = |e: ort::Error| Error::Inference(e.to_string());
+        let input_value = ort::value::Value::from_array(input_tensor).map_err(inference_error)?;
+        let outputs = self
+            .session
+            .run(ort::inputs!["input_1" => input_value])
+            .map_err(inference_error)?;
 
         // Get output.
-        let (_, data) = outputs[0].try_extract_tensor::<f32>()?;
+        let (_, data) = outputs[0]
+            .try_extract_tensor::<f32>()
+            .map_err(inference_error)?;
 
         let mut results = Vec::new();
         // Skip first 4 tags (ratings)
@@ -74,8 +129,49 @@ impl Tagger {
     }
 }
 
-// Preprocessing helper
-fn preprocess(image: &DynamicImage, config: &PreprocessConfig) -> Array4<f32> {
+fn input_shape_of(session: &Session) -> Option<Vec<i64>> {
+    let input = session.inputs().first()?;
+    Some(input.dtype().tensor_shape()?.to_vec())
+}
+
+/// Reads the input shape of the model at `model_path` without preparing it for
+/// inference, which is much cheaper than constructing a [`Tagger`].
+pub fn model_input_shape(model_path: &Path) -> Result<Option<Vec<i64>>> {
+    let model_error = |message: String| Error::ModelLoad {
+        path: model_path.to_path_buf(),
+        message,
+    };
+    let session = Session::builder()
+        .map_err(|e| model_error(format!("Failed to build session: {:?}", e)))?
+        .with_optimization_level(GraphOptimizationLevel::Disable)
+        .map_err(|e| model_error(format!("Failed to set optimization level: {:?}", e)))?
+        .commit_from_file(model_path)
+        .map_err(|e| model_error(e.to_string()))?;
+    Ok(input_shape_of(&session))
+}
+
+/// Drops excluded tags and applies the configured separator style, keeping scores.
+pub fn postprocess_tags(
+    results: Vec<(String, f32)>,
+    exclusion_list: &[String],
+    use_underscore: bool,
+) -> Vec<(String, f32)> {
+    results
+        .into_iter()
+        .filter(|(t, _)| !exclusion_list.contains(t))
+        .map(|(t, score)| {
+            let tag = if use_underscore {
+                t.replace(" ", "_")
+            } else {
+                t.replace("_", " ")
+            };
+            (tag, score)
+        })
+        .collect()
+}
+
+/// Resizes `image` to the model's input size and lays it out as an NHWC tensor.
+pub fn preprocess(image: &DynamicImage, config: &PreprocessConfig) -> Array4<f32> {
     let size = config.input_size;
     let resized = image.resize_exact(size, size, image::imageops::FilterType::CatmullRom);
 
@@ -87,16 +183,12 @@ fn preprocess(image: &DynamicImage, config: &PreprocessConfig) -> Array4<f32> {
         let g = pixel[1] as f32 / normalize_factor;
         let b = pixel[2] as f32 / normalize_factor;
 
-        if config.format == "bgr" {
-            // BGR order
-            input[[0, y as usize, x as usize, 0]] = b;
-            input[[0, y as usize, x as usize, 1]] = g;
-            input[[0, y as usize, x as usize, 2]] = r;
-        } else {
-            // Assume RGB
-            input[[0, y as usize, x as usize, 0]] = r;
-            input[[0, y as usize, x as usize, 1]] = g;
-            input[[0, y as usize, x as usize, 2]] = b;
+        let channels = match config.format {
+            PixelFormat::Bgr => [b, g, r],
+            PixelFormat::Rgb => [r, g, b],
+        };
+        for (c, value) in channels.into_iter().enumerate() {
+            input[[0, y as usize, x as usize, c]] = value;
         }
     }
 
@@ -122,7 +214,7 @@ mod tests {
         // or we manually create config to test specific behavior
         let config = PreprocessConfig {
             input_size: 448,
-            format: "bgr".to_string(),
+            format: PixelFormat::Bgr,
             normalize: false,
         };
 
@@ -147,7 +239,7 @@ mod tests {
         let dynamic_img = DynamicImage::ImageRgb8(img);
         let config = PreprocessConfig {
             input_size: 224,
-            format: "rgb".to_string(),
+            format: PixelFormat::Rgb,
             normalize: true, // Normalized [0, 1]
         };
 
@@ -161,9 +253,28 @@ mod tests {
         assert_eq!(tensor[[0, 0, 0, 2]], 0.0); // B
     }
 
+    #[test]
+    fn test_postprocess_tags() {
+        let results = vec![
+            ("long_hair".to_string(), 0.9),
+            ("lowres".to_string(), 0.8),
+            ("blue eyes".to_string(), 0.7),
+        ];
+        let exclusions = vec!["lowres".to_string()];
+
+        let spaced = postprocess_tags(results.clone(), &exclusions, false);
+        assert_eq!(
+            spaced,
+            vec![("long hair".to_string(), 0.9), ("blue eyes".to_string(), 0.7)]
+        );
+
+        let underscored = postprocess_tags(results, &exclusions, true);
+        assert_eq!(underscored[1].0, "blue_eyes");
+    }
+
     #[tokio::test]
     #[ignore] // Requires model files and runtime environment
-    async fn test_inference_performance() -> Result<(), Box<dyn std::error::Error>> {
+    async fn test_inference_performance() -> std::result::Result<(), Box<dyn std::error::Error>> {
         use std::time::Instant;
 
         // Paths should be adjusted to where models are expected during test
@@ -178,7 +289,7 @@ mod tests {
 
         let config = PreprocessConfig {
             input_size: 448,
-            format: "bgr".to_string(),
+            format: PixelFormat::Bgr,
             normalize: false,
         };
         let mut tagger = Tagger::new(model_path, tags_path, config)?;
@@ -0,0 +1,3 @@
+pub mod overrides;
+pub mod profiles;
+pub mod settings;
@@ -0,0 +1,109 @@
+// Per-invocation settings given on the command line or in the environment. Shared
+// by the desktop app's command line and `omni-tagger-cli`.
+use crate::settings::AppConfig;
+
+/// Settings that override `config.json` for a single run; nothing is saved.
+/// Environment variables apply when the flag is not given.
+#[derive(clap::Args, Debug, Clone, Default, PartialEq)]
+pub struct ConfigOverrides {
+    /// Confidence threshold between 0 and 1
+    #[arg(long, env = "OMNI_TAGGER_THRESHOLD")]
+    pub threshold: Option<f32>,
+
+    /// Model file (.onnx); relative paths are resolved like `model_path` in config.json
+    #[arg(long, value_name = "PATH", env = "OMNI_TAGGER_MODEL")]
+    pub model: Option<String>,
+
+    /// Tags file (.csv) belonging to --model
+    #[arg(long, value_name = "PATH", env = "OMNI_TAGGER_TAGS")]
+    pub tags: Option<String>,
+
+    /// Keep underscores in tags ("long_hair")
+    #[arg(long, overrides_with = "no_underscore")]
+    pub underscore: bool,
+
+    /// Replace underscores with spaces ("long hair")
+    #[arg(long, overrides_with = "underscore")]
+    pub no_underscore: bool,
+
+    /// Tags to exclude in addition to the configured exclusion list (repeatable,
+    /// or comma separated)
+    #[arg(long, value_name = "TAGS", value_delimiter = ',', env = "OMNI_TAGGER_EXCLUDE")]
+    pub exclude: Vec<String>,
+}
+
+impl ConfigOverrides {
+    /// Returns `config` with the overrides applied.
+    pub fn apply(&self, config: &AppConfig) -> AppConfig {
+        let mut config = config.clone();
+        if let Some(threshold) = self.threshold {
+            config.threshold = threshold;
+        }
+        if let Some(model) = &self.model {
+            config.model_path = model.clone();
+        }
+        if let Some(tags) = &self.tags {
+            config.tags_path = tags.clone();
+        }
+        if self.underscore {
+            config.use_underscore = true;
+        } else if self.no_underscore {
+            config.use_underscore = false;
+        }
+        for tag in &self.exclude {
+            let tag = tag.trim();
+            if !tag.is_empty() && !config.exclusion_list.iter().any(|t| t == tag) {
+                config.exclusion_list.push(tag.to_string());
+            }
+        }
+        config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[derive(Parser)]
+    struct Args {
+        #[command(flatten)]
+        overrides: ConfigOverrides,
+    }
+
+    fn parse(args: &[&str]) -> ConfigOverrides {
+        Args::try_parse_from(std::iter::once("test").chain(args.iter().copied()))
+            .unwrap()
+            .overrides
+    }
+
+    #[test]
+    fn test_apply_overrides() {
+        let config = AppConfig {
+            exclusion_list: vec!["lowres".to_string()],
+            ..Default::default()
+        };
+
+        let overrides = parse(&[
+            "--threshold",
+            "0.6",
+            "--model",
+            "/models/vit.onnx",
+            "--underscore",
+            "--exclude",
+            "monochrome, lowres",
+            "--exclude=text",
+        ]);
+        let merged = overrides.apply(&config);
+        assert_eq!(merged.threshold, 0.6);
+        assert_eq!(merged.model_path, "/models/vit.onnx");
+        assert_eq!(merged.tags_path, config.tags_path);
+        assert!(merged.use_underscore);
+        assert_eq!(merged.exclusion_list, vec!["lowres", "monochrome", "text"]);
+
+        // The last of --underscore/--no-underscore wins; neither keeps the config value
+        assert!(!parse(&["--underscore", "--no-underscore"]).apply(&merged).use_underscore);
+        assert!(parse(&[]).apply(&merged).use_underscore);
+        assert_eq!(parse(&[]).apply(&config).threshold, config.threshold);
+    }
+}
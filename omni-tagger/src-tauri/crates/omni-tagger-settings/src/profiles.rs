@@ -0,0 +1,273 @@
+// Named settings profiles, stored as `profiles/<name>.json` next to `config.json`.
+// Shared by the desktop app and `omni-tagger-cli`.
+use crate::settings::{AppConfig, PreprocessConfig};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Longest profile name accepted; names double as file names.
+const MAX_NAME_LEN: usize = 64;
+
+/// Device names Windows does not allow as file names, whatever the extension.
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM0", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7",
+    "COM8", "COM9", "LPT0", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8",
+    "LPT9",
+];
+
+#[derive(Debug, thiserror::Error)]
+pub enum ProfileError {
+    #[error("Invalid profile name {0:?}: use letters, digits, spaces, '-' or '_' (at most 64 characters)")]
+    InvalidName(String),
+    #[error("Invalid profile name {0:?}: the name is reserved on Windows")]
+    ReservedName(String),
+    #[error("Profile {name:?} conflicts with the existing profile {existing:?}")]
+    NameConflict { name: String, existing: String },
+    #[error("Profile {0:?} does not exist")]
+    NotFound(String),
+    #[error("Profile {name:?} is not valid: {source}")]
+    Invalid {
+        name: String,
+        source: serde_json::Error,
+    },
+    #[error("{context}: {source}")]
+    Io {
+        context: String,
+        source: std::io::Error,
+    },
+}
+
+/// The tagging settings a profile switches; everything else (integrations, network,
+/// watch folders) stays as configured.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct Profile {
+    pub threshold: f32,
+    pub use_underscore: bool,
+    pub exclusion_list: Vec<String>,
+    pub model_path: String,
+    pub tags_path: String,
+    pub preprocessing: PreprocessConfig,
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Self::from_config(&AppConfig::default())
+    }
+}
+
+impl Profile {
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self {
+            threshold: config.threshold,
+            use_underscore: config.use_underscore,
+            exclusion_list: config.exclusion_list.clone(),
+            model_path: config.model_path.clone(),
+            tags_path: config.tags_path.clone(),
+            preprocessing: config.preprocessing.clone(),
+        }
+    }
+
+    /// Returns `config` with this profile's settings applied.
+    pub fn apply(&self, config: &AppConfig) -> AppConfig {
+        AppConfig {
+            threshold: self.threshold,
+            use_underscore: self.use_underscore,
+            exclusion_list: self.exclusion_list.clone(),
+            model_path: self.model_path.clone(),
+            tags_path: self.tags_path.clone(),
+            preprocessing: self.preprocessing.clone(),
+            ..config.clone()
+        }
+    }
+}
+
+/// Directory holding the profiles of the config file in `config_dir`.
+pub fn profiles_dir(config_dir: &Path) -> PathBuf {
+    config_dir.join("profiles")
+}
+
+pub fn validate_name(name: &str) -> Result<(), ProfileError> {
+    let valid = !name.trim().is_empty()
+        && name.trim() == name
+        && name.chars().count() <= MAX_NAME_LEN
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == ' ' || c == '-' || c == '_');
+    if !valid {
+        return Err(ProfileError::InvalidName(name.to_string()));
+    }
+    if RESERVED_NAMES.iter().any(|r| r.eq_ignore_ascii_case(name)) {
+        return Err(ProfileError::ReservedName(name.to_string()));
+    }
+    Ok(())
+}
+
+fn profile_path(dir: &Path, name: &str) -> Result<PathBuf, ProfileError> {
+    validate_name(name)?;
+    Ok(dir.join(format!("{}.json", name)))
+}
+
+/// Names of all profiles in `dir`, sorted. A missing directory means no profiles.
+pub fn list(dir: &Path) -> Result<Vec<String>, ProfileError> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => {
+            return Err(ProfileError::Io {
+                context: format!("Failed to read {:?}", dir),
+                source: e,
+            })
+        }
+    };
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let path = entry.path();
+            if path.extension()? != "json" {
+                return None;
+            }
+            let name = path.file_stem()?.to_str()?.to_string();
+            validate_name(&name).is_ok().then_some(name)
+        })
+        .collect();
+    names.sort_by_key(|name| name.to_lowercase());
+    Ok(names)
+}
+
+pub fn load(dir: &Path, name: &str) -> Result<Profile, ProfileError> {
+    let path = profile_path(dir, name)?;
+    let content = match std::fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(ProfileError::NotFound(name.to_string()))
+        }
+        Err(e) => {
+            return Err(ProfileError::Io {
+                context: format!("Failed to read {:?}", path),
+                source: e,
+            })
+        }
+    };
+    serde_json::from_str(&content).map_err(|e| ProfileError::Invalid {
+        name: name.to_string(),
+        source: e,
+    })
+}
+
+/// Writes `profile` as `name`, replacing a profile of exactly that name. A name
+/// that differs from an existing one only in case is refused, as both would map to
+/// the same file on case-insensitive file systems.
+pub fn save(dir: &Path, name: &str, profile: &Profile) -> Result<(), ProfileError> {
+    let path = profile_path(dir, name)?;
+    let lower = name.to_lowercase();
+    if let Some(existing) = list(dir)?
+        .into_iter()
+        .find(|n| n != name && n.to_lowercase() == lower)
+    {
+        return Err(ProfileError::NameConflict {
+            name: name.to_string(),
+            existing,
+        });
+    }
+    std::fs::create_dir_all(dir).map_err(|e| ProfileError::Io {
+        context: format!("Failed to create {:?}", dir),
+        source: e,
+    })?;
+    let content = serde_json::to_string_pretty(profile).map_err(|e| ProfileError::Invalid {
+        name: name.to_string(),
+        source: e,
+    })?;
+    std::fs::write(&path, content).map_err(|e| ProfileError::Io {
+        context: format!("Failed to write {:?}", path),
+        source: e,
+    })
+}
+
+pub fn delete(dir: &Path, name: &str) -> Result<(), ProfileError> {
+    let path = profile_path(dir, name)?;
+    match std::fs::remove_file(&path) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            Err(ProfileError::NotFound(name.to_string()))
+        }
+        Err(e) => Err(ProfileError::Io {
+            context: format!("Failed to delete {:?}", path),
+            source: e,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_name() {
+        for name in ["dataset captioning", "prompt-copy", "SD_1", "タグ"] {
+            assert!(validate_name(name).is_ok(), "{}", name);
+        }
+        for name in ["", " padded", "../config", "a/b", "x.json", &"a".repeat(65)] {
+            assert!(validate_name(name).is_err(), "{}", name);
+        }
+        for name in ["CON", "nul", "Com1", "LPT9"] {
+            assert!(
+                matches!(validate_name(name), Err(ProfileError::ReservedName(_))),
+                "{}",
+                name
+            );
+        }
+        assert!(validate_name("console").is_ok());
+        assert!(validate_name("COM10").is_ok());
+    }
+
+    #[test]
+    fn test_save_load_apply() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = std::env::temp_dir().join("omni_tagger_test_profiles");
+        let _ = std::fs::remove_dir_all(&dir);
+        assert!(list(&dir)?.is_empty());
+
+        let captioning = Profile {
+            threshold: 0.2,
+            use_underscore: true,
+            ..Default::default()
+        };
+        save(&dir, "dataset captioning", &captioning)?;
+        save(&dir, "Prompt copy", &Profile::default())?;
+        std::fs::write(dir.join("notes.txt"), "ignored")?;
+        assert_eq!(list(&dir)?, vec!["dataset captioning", "Prompt copy"]);
+
+        let loaded = load(&dir, "dataset captioning")?;
+        assert_eq!(loaded, captioning);
+        let config = loaded.apply(&AppConfig {
+            watch_enabled: true,
+            ..Default::default()
+        });
+        assert_eq!(config.threshold, 0.2);
+        assert!(config.use_underscore);
+        assert!(config.watch_enabled);
+
+        // Hand-written profiles may leave settings out
+        std::fs::write(dir.join("partial.json"), r#"{"threshold": 0.5}"#)?;
+        assert_eq!(load(&dir, "partial")?.threshold, 0.5);
+
+        // Overwriting is fine, a name differing only in case is not
+        save(&dir, "Prompt copy", &Profile::default())?;
+        assert!(matches!(
+            save(&dir, "prompt COPY", &Profile::default()),
+            Err(ProfileError::NameConflict { existing, .. }) if existing == "Prompt copy"
+        ));
+
+        delete(&dir, "Prompt copy")?;
+        assert!(matches!(
+            load(&dir, "Prompt copy"),
+            Err(ProfileError::NotFound(_))
+        ));
+        assert!(matches!(
+            load(&dir, "../config"),
+            Err(ProfileError::InvalidName(_))
+        ));
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
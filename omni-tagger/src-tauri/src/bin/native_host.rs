@@ -1,5 +1,6 @@
 use base64::{engine::general_purpose, Engine as _};
 use byteorder::{NativeEndian, ReadBytesExt, WriteBytesExt};
+use image::ImageFormat;
 use serde::{Deserialize, Serialize};
 use std::env;
 use std::fs;
@@ -17,6 +18,20 @@ struct Request {
 struct Response {
     status: String,
     message: String,
+    /// Error code shared with the app's `AppError` codes where applicable, so the
+    /// extension can tell failures apart without parsing the message.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code: Option<&'static str>,
+}
+
+impl Response {
+    fn error(code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            status: "error".to_string(),
+            message: message.into(),
+            code: Some(code),
+        }
+    }
 }
 
 fn main() -> io::Result<()> {
@@ -31,10 +46,10 @@ fn main() -> io::Result<()> {
         // Enforce maximum payload size to prevent OOM (e.g., 20 MB)
         const MAX_PAYLOAD_SIZE: usize = 20 * 1024 * 1024;
         if length > MAX_PAYLOAD_SIZE {
-            let response = Response {
-                status: "error".to_string(),
-                message: format!("Payload size {} exceeds maximum allowed size of {}", length, MAX_PAYLOAD_SIZE),
-            };
+            let response = Response::error(
+                "PayloadTooLarge",
+                format!("Payload size {} exceeds maximum allowed size of {}", length, MAX_PAYLOAD_SIZE),
+            );
             send_response(&response)?;
 
             // Consume the oversized payload so the stream isn't left in a bad state
@@ -57,10 +72,7 @@ fn main() -> io::Result<()> {
 
         let response = match serde_json::from_str::<Request>(&request_str) {
             Ok(req) => handle_request(req),
-            Err(e) => Response {
-                status: "error".to_string(),
-                message: format!("Invalid JSON: {}", e),
-            },
+            Err(e) => Response::error("InvalidRequest", format!("Invalid JSON: {}", e)),
         };
 
         send_response(&response)?;
@@ -77,21 +89,20 @@ fn handle_request(req: Request) -> Response {
     } else if let Some(data) = req.data {
         if data.starts_with("data:") {
             if let Some(comma_idx) = data.find(',') {
-                let header = &data[..comma_idx];
                 let base64_data = &data[comma_idx + 1..];
 
-                let extension = if header.contains("image/png") {
-                    "png"
-                } else if header.contains("image/jpeg") || header.contains("image/jpg") {
-                    "jpg"
-                } else if header.contains("image/webp") {
-                    "webp"
-                } else {
-                    "png"
-                };
-
                 match general_purpose::STANDARD.decode(base64_data) {
                     Ok(decoded) => {
+                        // The declared MIME type is not trusted; the content decides
+                        let extension = match sniff_extension(&decoded) {
+                            Some(extension) => extension,
+                            None => {
+                                return Response::error(
+                                    "UnsupportedImage",
+                                    "data is not a PNG, JPEG, WebP, GIF, BMP or TIFF image",
+                                )
+                            }
+                        };
                         let timestamp = SystemTime::now()
                             .duration_since(UNIX_EPOCH)
                             .unwrap_or_default()
@@ -106,37 +117,31 @@ fn handle_request(req: Request) -> Response {
                                 command_args.push(file_path.to_string_lossy().into_owned());
                             }
                             Err(e) => {
-                                return Response {
-                                    status: "error".to_string(),
-                                    message: format!("Failed to write temp file: {}", e),
-                                }
+                                return Response::error(
+                                    "Io",
+                                    format!("Failed to write temp file: {}", e),
+                                )
                             }
                         }
                     }
                     Err(e) => {
-                        return Response {
-                            status: "error".to_string(),
-                            message: format!("Failed to decode base64: {}", e),
-                        }
+                        return Response::error(
+                            "UnsupportedImage",
+                            format!("Failed to decode base64: {}", e),
+                        )
                     }
                 }
             } else {
-                return Response {
-                    status: "error".to_string(),
-                    message: "Invalid data URI format".to_string(),
-                };
+                return Response::error("InvalidRequest", "Invalid data URI format");
             }
         } else {
-            return Response {
-                status: "error".to_string(),
-                message: "Invalid data URI format (must start with data:)".to_string(),
-            };
+            return Response::error(
+                "InvalidRequest",
+                "Invalid data URI format (must start with data:)",
+            );
         }
     } else {
-        return Response {
-            status: "error".to_string(),
-            message: "No URL or data provided".to_string(),
-        };
+        return Response::error("InvalidRequest", "No URL or data provided");
     };
 
     // Determine path to main executable
@@ -144,10 +149,7 @@ fn handle_request(req: Request) -> Response {
     let current_exe = match env::current_exe() {
         Ok(p) => p,
         Err(e) => {
-            return Response {
-                status: "error".to_string(),
-                message: format!("Failed to get exe path: {}", e),
-            }
+            return Response::error("Io", format!("Failed to get exe path: {}", e))
         }
     };
 
@@ -189,13 +191,13 @@ fn handle_request(req: Request) -> Response {
     let app_path = match found_path {
         Some(p) => p,
         None => {
-            return Response {
-                status: "error".to_string(),
-                message: format!(
+            return Response::error(
+                "AppNotFound",
+                format!(
                     "App executable not found. Searched at {:?}, {:?} (and system paths on Linux)",
                     app_path_local, app_path_parent
                 ),
-            };
+            );
         }
     };
 
@@ -212,11 +214,24 @@ fn handle_request(req: Request) -> Response {
         Ok(_) => Response {
             status: "ok".to_string(),
             message: "Processing started".to_string(),
+            code: None,
         },
-        Err(e) => Response {
-            status: "error".to_string(),
-            message: format!("Failed to launch app: {}", e),
-        },
+        Err(e) => Response::error("LaunchFailed", format!("Failed to launch app: {}", e)),
+    }
+}
+
+/// Picks the temp file extension from the magic bytes of `bytes`. The app checks
+/// the image again when it decodes it; this only keeps the host from handing it
+/// files that are not images at all, without linking the tagging engine.
+fn sniff_extension(bytes: &[u8]) -> Option<&'static str> {
+    match image::guess_format(bytes).ok()? {
+        ImageFormat::Png => Some("png"),
+        ImageFormat::Jpeg => Some("jpg"),
+        ImageFormat::WebP => Some("webp"),
+        ImageFormat::Gif => Some("gif"),
+        ImageFormat::Bmp => Some("bmp"),
+        ImageFormat::Tiff => Some("tiff"),
+        _ => None,
     }
 }
 
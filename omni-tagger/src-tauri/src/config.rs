@@ -1,76 +1,165 @@
+use crate::error::AppError;
 use crate::model_manager;
 use crate::state::AppState;
-use crate::tagger::Tagger;
-use serde::{Deserialize, Serialize};
+use omni_tagger_core::catalog::{Catalog, ModelEntry};
+use omni_tagger_core::hub;
+use omni_tagger_core::inventory::{self, ModelInfo};
+use omni_tagger_core::Tagger;
+use omni_tagger_settings::profiles::{self, Profile};
+pub use omni_tagger_settings::settings::{
+    ApiServerConfig, AppConfig, ModelHubConfig, PreprocessConfig,
+};
+use omni_tagger_settings::settings::{
+    self, parse_config, ConfigSection, FieldError, CONFIG_VERSION,
+};
+use serde::Serialize;
 use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 use tauri::{path::BaseDirectory, AppHandle, Manager, State};
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
-pub struct PreprocessConfig {
-    pub input_size: u32,
-    pub format: String, // "bgr" or "rgb"
-    pub normalize: bool,
+/// Why `config.json` could not be used and the defaults were loaded instead.
+/// Sent with the `config-reset` event and returned by `get_config_reset`.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfigReset {
+    pub reason: String,
+    /// Where the unreadable file was moved to, if it could be moved
+    pub backup_path: Option<String>,
 }
 
-impl Default for PreprocessConfig {
-    fn default() -> Self {
-        Self {
-            input_size: 448,
-            format: "bgr".to_string(),
-            normalize: false,
+/// Loads `config.json`, migrating it if it was written by an older version. A file
+/// that cannot be read or parsed is moved aside and the defaults are used; the
+/// returned [`ConfigReset`] tells the user what happened.
+pub fn load_config(app: &AppHandle) -> (AppConfig, Option<ConfigReset>) {
+    let path = match app.path().resolve("config.json", BaseDirectory::AppConfig) {
+        Ok(path) => path,
+        Err(e) => {
+            eprintln!("Failed to resolve config path: {}", e);
+            return (AppConfig::default(), None);
         }
+    };
+    if !path.exists() {
+        return (AppConfig::default(), None);
     }
-}
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct AppConfig {
-    pub model_path: String,
-    pub tags_path: String,
-    pub threshold: f32,
-    pub use_underscore: bool,
-    pub exclusion_list: Vec<String>,
-    #[serde(default)]
-    pub preprocessing: PreprocessConfig,
+    let parsed = fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read {:?}: {}", path, e))
+        .and_then(|content| {
+            parse_config(&content).map_err(|e| format!("Failed to parse {:?}: {}", path, e))
+        });
+    match parsed {
+        Ok((mut config, from)) => {
+            if from < u64::from(CONFIG_VERSION) {
+                migrate_file(app, &path, &config, from);
+            }
+            if ensure_api_token(&mut config) {
+                if let Err(e) = save_config(app, &config) {
+                    eprintln!("Failed to save the generated API token: {}", e);
+                }
+            }
+            (config, None)
+        }
+        Err(reason) => {
+            eprintln!("{}; using default settings", reason);
+            let backup_path = match backup_config(&path, &unix_time().to_string(), true) {
+                Ok(backup) => {
+                    println!("Moved unreadable config to {:?}", backup);
+                    Some(backup.display().to_string())
+                }
+                Err(e) => {
+                    eprintln!("Failed to back up {:?}: {}", path, e);
+                    None
+                }
+            };
+            (
+                AppConfig::default(),
+                Some(ConfigReset {
+                    reason,
+                    backup_path,
+                }),
+            )
+        }
+    }
 }
 
-impl Default for AppConfig {
-    fn default() -> Self {
-        Self {
-            model_path: "models/model.onnx".to_string(),
-            tags_path: "models/tags.csv".to_string(),
-            threshold: 0.35,
-            use_underscore: false,
-            exclusion_list: Vec::new(),
-            preprocessing: PreprocessConfig::default(),
+/// Gives an enabled API server without a token a random one (e.g. in a file written
+/// by an older version, which served such configurations unauthenticated). Returns
+/// true if a token was generated.
+fn ensure_api_token(config: &mut AppConfig) -> bool {
+    if !config.api_server.enabled || !config.api_server.token.is_empty() {
+        return false;
+    }
+    match crate::server::generate_token() {
+        Ok(token) => {
+            config.api_server.token = token;
+            println!("Generated a bearer token for the API server");
+            true
+        }
+        Err(e) => {
+            // The server refuses to start without a token
+            eprintln!("{}", e);
+            false
         }
     }
 }
 
-pub fn load_config(app: &AppHandle) -> AppConfig {
-    if let Ok(path) = app.path().resolve("config.json", BaseDirectory::AppConfig) {
-        if path.exists() {
-            if let Ok(content) = fs::read_to_string(&path) {
-                if let Ok(config) = serde_json::from_str(&content) {
-                    return config;
-                }
-            }
+/// Keeps a copy of the old file and rewrites it in the current format.
+fn migrate_file(app: &AppHandle, path: &Path, config: &AppConfig, from: u64) {
+    match backup_config(path, &format!("v{}", from), false) {
+        Ok(backup) => println!(
+            "Migrated config from version {} to {} (previous file kept at {:?})",
+            from, CONFIG_VERSION, backup
+        ),
+        Err(e) => {
+            // Leave the file alone; it is migrated again on the next start
+            eprintln!("Failed to back up {:?} before migrating: {}", path, e);
+            return;
         }
     }
-    AppConfig::default()
+    if let Err(e) = save_config(app, config) {
+        eprintln!("Failed to save migrated config: {}", e);
+    }
+}
+
+/// Copies (or moves) `config.json` to `config.json.<suffix>.bak` next to it.
+fn backup_config(path: &Path, suffix: &str, move_file: bool) -> std::io::Result<PathBuf> {
+    let backup = path.with_file_name(format!("config.json.{}.bak", suffix));
+    if move_file {
+        fs::rename(path, &backup)?;
+    } else {
+        fs::copy(path, &backup)?;
+    }
+    Ok(backup)
 }
 
-pub fn save_config(app: &AppHandle, config: &AppConfig) -> Result<(), String> {
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Reads a config file other than the app's own, e.g. one given with `--config`.
+pub fn read_config_file(path: &Path) -> Result<AppConfig, AppError> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| AppError::Io(format!("Failed to read {:?}: {}", path, e)))?;
+    let (config, _) = parse_config(&content)
+        .map_err(|e| AppError::Config(format!("Failed to parse {:?}: {}", path, e)))?;
+    Ok(config)
+}
+
+pub fn save_config(app: &AppHandle, config: &AppConfig) -> Result<(), AppError> {
     let path = app
         .path()
         .resolve("config.json", BaseDirectory::AppConfig)
-        .map_err(|e| e.to_string())?;
+        .map_err(|e| AppError::Config(e.to_string()))?;
 
     if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        fs::create_dir_all(parent).map_err(|e| AppError::Io(format!("Failed to create {:?}: {}", parent, e)))?;
     }
 
-    let content = serde_json::to_string_pretty(config).map_err(|e| e.to_string())?;
-    fs::write(path, content).map_err(|e| e.to_string())?;
+    let content = serde_json::to_string_pretty(config).map_err(|e| AppError::Config(e.to_string()))?;
+    fs::write(&path, content).map_err(|e| AppError::Io(format!("Failed to write {:?}: {}", path, e)))?;
     Ok(())
 }
 
@@ -94,39 +183,104 @@ pub fn resolve_model_path(app: &AppHandle, path_str: &str) -> std::path::PathBuf
 }
 
 #[tauri::command]
-pub fn get_config(state: State<'_, AppState>) -> Result<AppConfig, String> {
+pub fn get_config(state: State<'_, AppState>) -> Result<AppConfig, AppError> {
     state
         .config
         .lock()
-        .map_err(|e| e.to_string())
+        .map_err(|e| AppError::Internal(e.to_string()))
         .map(|c| c.clone())
 }
 
+/// The reason the settings were reset to defaults at startup, if they were.
 #[tauri::command]
-pub async fn set_config(
-    app: AppHandle,
-    state: State<'_, AppState>,
-    config: AppConfig,
-) -> Result<(), String> {
-    let mut config_guard = state.config.lock().map_err(|e| e.to_string())?;
+pub fn get_config_reset(state: State<'_, AppState>) -> Result<Option<ConfigReset>, AppError> {
+    state
+        .config_reset
+        .lock()
+        .map_err(|e| AppError::Internal(e.to_string()))
+        .map(|r| r.clone())
+}
+
+/// Runs [`AppConfig::validate`] and checks that the model and tags files exist. A
+/// missing file is accepted if the catalog can download it, which happens when a
+/// model preset is selected before it was downloaded.
+pub fn validate_config(app: &AppHandle, config: &AppConfig) -> Result<(), AppError> {
+    let mut errors = config.validate();
+    let catalog = model_manager::load_catalog(app)?;
+    let model_path = resolve_model_path(app, &config.model_path);
+    let downloadable = catalog
+        .find_by_model_path(&model_path)
+        .filter(|e| !e.model_url.is_empty());
+    if !config.model_path.trim().is_empty() && !model_path.exists() && downloadable.is_none() {
+        errors.push(FieldError::new(
+            "model_path",
+            format!("{} does not exist", model_path.display()),
+        ));
+    }
+    let tags_path = resolve_model_path(app, &config.tags_path);
+    let downloadable = catalog
+        .find_by_tags_path(&tags_path)
+        .filter(|e| !e.tags_url.is_empty());
+    if !config.tags_path.trim().is_empty() && !tags_path.exists() && downloadable.is_none() {
+        errors.push(FieldError::new(
+            "tags_path",
+            format!("{} does not exist", tags_path.display()),
+        ));
+    }
+    if let Err(e) = config.exclusions(&config_dir(app)?) {
+        errors.push(FieldError::new("exclusion_file", e.to_string()));
+    }
+    if config.api_server.enabled && config.api_server.token.is_empty() {
+        errors.push(FieldError::new(
+            "api_server.token",
+            "must not be empty while the API server is enabled",
+        ));
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(AppError::InvalidConfig(errors))
+    }
+}
+
+#[tauri::command]
+pub async fn set_config(app: AppHandle, config: AppConfig) -> Result<(), AppError> {
+    update_config(&app, config)
+}
+
+/// Validates and saves `config`, reloading the tagger if the model changed. Changes
+/// to the tagging settings are also written to the active profile.
+pub fn update_config(app: &AppHandle, config: AppConfig) -> Result<(), AppError> {
+    validate_config(app, &config)?;
+    if let Some(name) = &config.active_profile {
+        profiles::save(&profiles_dir(app)?, name, &Profile::from_config(&config))?;
+    }
+
+    let state = app.state::<AppState>();
+    let mut config_guard = state.config.lock().map_err(|e| AppError::Internal(e.to_string()))?;
 
     let should_reload_tagger = config_guard.model_path != config.model_path
         || config_guard.tags_path != config.tags_path
         || config_guard.preprocessing != config.preprocessing;
 
     *config_guard = config.clone();
-    save_config(&app, &config)?;
+    save_config(app, &config)?;
+    drop(config_guard);
 
     if should_reload_tagger {
-        let mut tagger_guard = state.tagger.lock().map_err(|e| e.to_string())?;
-        let model_path = resolve_model_path(&app, &config.model_path);
-        let tags_path = resolve_model_path(&app, &config.tags_path);
+        let model_path = resolve_model_path(app, &config.model_path);
+        let tags_path = resolve_model_path(app, &config.tags_path);
 
-        match Tagger::new(
+        // Load without holding the locks so running inference, get_config and the
+        // HTTP API's status checks are not held up by the load
+        let loaded = Tagger::new(
             model_path.to_str().unwrap_or(&config.model_path),
             tags_path.to_str().unwrap_or(&config.tags_path),
             config.preprocessing.clone(),
-        ) {
+        );
+        let mut tagger_guard = state.tagger.lock().map_err(|e| AppError::Internal(e.to_string()))?;
+        match loaded {
             Ok(tagger) => {
                 *tagger_guard = Some(tagger);
                 println!("Tagger reloaded successfully from {:?}", model_path);
@@ -134,30 +288,339 @@ pub async fn set_config(
             Err(e) => {
                 println!("Failed to reload tagger: {}", e);
                 *tagger_guard = None;
-                return Err(format!("Failed to reload tagger: {}", e));
+                return Err(e.into());
             }
         }
     }
     Ok(())
 }
 
+/// Directory holding `config.json`; relative `exclusion_file` paths start here.
+fn config_dir(app: &AppHandle) -> Result<PathBuf, AppError> {
+    app.path()
+        .app_config_dir()
+        .map_err(|e| AppError::Config(e.to_string()))
+}
+
+pub fn profiles_dir(app: &AppHandle) -> Result<PathBuf, AppError> {
+    Ok(profiles::profiles_dir(&config_dir(app)?))
+}
+
+/// Tags excluded from the output: the exclusion list and the exclusion file.
+pub fn exclusions(app: &AppHandle, config: &AppConfig) -> Result<Vec<String>, AppError> {
+    Ok(config.exclusions(&config_dir(app)?)?)
+}
+
+/// Writes the settings, or only the given sections, to `path` for sharing (see
+/// [`settings::export_config`]).
+#[tauri::command]
+pub fn export_config(
+    state: State<'_, AppState>,
+    path: String,
+    sections: Vec<ConfigSection>,
+) -> Result<(), AppError> {
+    let config = get_config(state)?;
+    let exported = settings::export_config(&config, &sections)?;
+    let content = serde_json::to_string_pretty(&exported).map_err(|e| AppError::Config(e.to_string()))?;
+    fs::write(&path, content).map_err(|e| AppError::Io(format!("Failed to write {:?}: {}", path, e)))?;
+    println!("Exported settings to {:?}", path);
+    Ok(())
+}
+
+/// Applies the settings in the file at `path` (see [`settings::import_config`]),
+/// then validates and saves them like `set_config`.
+#[tauri::command]
+pub async fn import_config(
+    app: AppHandle,
+    path: String,
+    sections: Vec<ConfigSection>,
+    merge: bool,
+) -> Result<AppConfig, AppError> {
+    let content = fs::read_to_string(&path)
+        .map_err(|e| AppError::Io(format!("Failed to read {:?}: {}", path, e)))?;
+    let config = get_config(app.state::<AppState>())?;
+    let config = settings::import_config(&config, &content, &sections, merge)
+        .map_err(|e| AppError::Config(format!("Failed to import {:?}: {}", path, e)))?;
+    update_config(&app, config.clone())?;
+    println!("Imported settings from {:?}", path);
+    use tauri::Emitter;
+    let _ = app.emit("config-changed", ());
+    Ok(config)
+}
+
+/// The config with profile `name` applied; nothing is saved.
+pub fn config_with_profile(
+    app: &AppHandle,
+    config: &AppConfig,
+    name: &str,
+) -> Result<AppConfig, AppError> {
+    Ok(profiles::load(&profiles_dir(app)?, name)?.apply(config))
+}
+
+#[tauri::command]
+pub fn list_profiles(app: AppHandle) -> Result<Vec<String>, AppError> {
+    Ok(profiles::list(&profiles_dir(&app)?)?)
+}
+
+/// Stores the current tagging settings as profile `name` and makes it active.
+#[tauri::command]
+pub fn save_profile(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    name: String,
+) -> Result<(), AppError> {
+    let config = get_config(state)?;
+    profiles::save(&profiles_dir(&app)?, &name, &Profile::from_config(&config))?;
+    update_config(
+        &app,
+        AppConfig {
+            active_profile: Some(name),
+            ..config
+        },
+    )?;
+    profiles_changed(&app);
+    Ok(())
+}
+
+/// Applies profile `name` to the settings and makes it active.
+#[tauri::command]
+pub async fn switch_profile(app: AppHandle, name: String) -> Result<(), AppError> {
+    switch_to_profile(&app, &name)
+}
+
+pub fn switch_to_profile(app: &AppHandle, name: &str) -> Result<(), AppError> {
+    let config = get_config(app.state::<AppState>())?;
+    let config = AppConfig {
+        active_profile: Some(name.to_string()),
+        ..config_with_profile(app, &config, name)?
+    };
+    update_config(app, config)?;
+    println!("Switched to profile {:?}", name);
+    profiles_changed(app);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn delete_profile(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    name: String,
+) -> Result<(), AppError> {
+    profiles::delete(&profiles_dir(&app)?, &name)?;
+    let config = get_config(state.clone())?;
+    if config.active_profile.as_deref() == Some(name.as_str()) {
+        let config = AppConfig {
+            active_profile: None,
+            ..config
+        };
+        save_config(&app, &config)?;
+        *state.config.lock().map_err(|e| AppError::Internal(e.to_string()))? = config;
+    }
+    profiles_changed(&app);
+    Ok(())
+}
+
+/// Refreshes the tray's profile submenu and tells an open settings window.
+fn profiles_changed(app: &AppHandle) {
+    if let Err(e) = crate::refresh_tray_menu(app) {
+        eprintln!("Failed to update tray menu: {}", e);
+    }
+    use tauri::Emitter;
+    let _ = app.emit("config-changed", ());
+}
+
 #[tauri::command]
-pub async fn check_model_exists(app: AppHandle, path_str: String) -> Result<bool, String> {
+pub async fn check_model_exists(app: AppHandle, path_str: String) -> Result<bool, AppError> {
     let path = resolve_model_path(&app, &path_str);
     Ok(model_manager::check_file_exists(&path))
 }
 
+/// Checks the configured and catalog model files against the checksums recorded
+/// when they were downloaded.
+#[tauri::command]
+pub async fn verify_models(
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<Vec<model_manager::ModelCheck>, AppError> {
+    let config = get_config(state)?;
+    let catalog = model_manager::load_catalog(&app)?;
+    let mut paths = vec![
+        resolve_model_path(&app, &config.model_path),
+        resolve_model_path(&app, &config.tags_path),
+    ];
+    for entry in &catalog.models {
+        for file in [&entry.model_path, &entry.tags_path] {
+            let path = resolve_model_path(&app, file);
+            if path.exists() && !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+    }
+
+    // Hashing several hundred megabytes takes a while; keep it off the async runtime
+    let checks = tauri::async_runtime::spawn_blocking(move || {
+        model_manager::verify_files(&paths, &catalog)
+    })
+    .await
+    .map_err(|e| AppError::Internal(e.to_string()))??;
+    Ok(checks)
+}
+
 #[tauri::command]
 pub async fn download_new_model(
     app: AppHandle,
     url: String,
     path_str: String,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     let path = resolve_model_path(&app, &path_str);
-    model_manager::download_file(&app, &url, &path)
-        .await
-        .map_err(|e| e.to_string())?;
+    model_manager::download_file(&app, &url, &path).await?;
+    use tauri::Emitter;
+    let _ = app.emit("model-download-finished", ());
+    Ok(())
+}
+
+/// Bundled model catalog merged with the user's `models.json`.
+#[tauri::command]
+pub fn get_model_catalog(app: AppHandle) -> Result<Vec<ModelEntry>, AppError> {
+    Ok(model_manager::load_catalog(&app)?.models)
+}
+
+#[tauri::command]
+pub async fn download_catalog_model(app: AppHandle, id: String) -> Result<(), AppError> {
+    let catalog = model_manager::load_catalog(&app)?;
+    let entry = catalog
+        .get(&id)
+        .ok_or_else(|| AppError::InvalidRequest(format!("Unknown model '{}'", id)))?;
+    let model_path = resolve_model_path(&app, &entry.model_path);
+    let tags_path = resolve_model_path(&app, &entry.tags_path);
+    model_manager::download_catalog_model(&app, entry, &model_path, &tags_path).await?;
     use tauri::Emitter;
     let _ = app.emit("model-download-finished", ());
     Ok(())
 }
+
+/// Downloads `model.onnx` and `selected_tags.csv` from a Hugging Face repository,
+/// adds the model to the user catalog and makes it the active model.
+#[tauri::command]
+pub async fn download_hub_model(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    repo_id: String,
+    revision: Option<String>,
+) -> Result<ModelEntry, AppError> {
+    let config = get_config(state)?;
+    let revision = revision
+        .filter(|r| !r.trim().is_empty())
+        .unwrap_or_else(|| hub::DEFAULT_REVISION.to_string());
+    let entry = hub::model_entry(&config.model_hub.base_url, repo_id.trim(), revision.trim())?;
+
+    let model_path = resolve_model_path(&app, &entry.model_path);
+    let tags_path = resolve_model_path(&app, &entry.tags_path);
+    model_manager::download_catalog_model(&app, &entry, &model_path, &tags_path).await?;
+    Catalog::register(&model_manager::user_catalog_path(&app)?, entry.clone())?;
+
+    let new_config = AppConfig {
+        model_path: entry.model_path.clone(),
+        tags_path: entry.tags_path.clone(),
+        preprocessing: entry.preprocessing.clone(),
+        ..config
+    };
+    update_config(&app, new_config)?;
+
+    use tauri::Emitter;
+    let _ = app.emit("model-download-finished", ());
+    let _ = app.emit("config-changed", ());
+    Ok(entry)
+}
+
+#[tauri::command]
+pub async fn list_installed_models(
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<model_manager::InstalledModels, AppError> {
+    let config = get_config(state)?;
+    let models_dir = model_manager::models_dir(&app)?;
+    let model_path = resolve_model_path(&app, &config.model_path);
+    let tags_path = resolve_model_path(&app, &config.tags_path);
+    let catalog = model_manager::load_catalog(&app)?;
+
+    // Hashing and opening every model takes a while; keep it off the async runtime
+    let installed = tauri::async_runtime::spawn_blocking(move || {
+        model_manager::list_installed(&models_dir, (&model_path, &tags_path), &catalog)
+    })
+    .await
+    .map_err(|e| AppError::Internal(e.to_string()))??;
+    Ok(installed)
+}
+
+#[tauri::command]
+pub async fn inspect_model(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    path_str: String,
+) -> Result<ModelInfo, AppError> {
+    let config = get_config(state)?;
+    let models_dir = model_manager::models_dir(&app)?;
+    let path = resolve_model_path(&app, &path_str);
+    if !path.exists() {
+        return Err(AppError::model_missing(&path));
+    }
+    let tags_path = (path == resolve_model_path(&app, &config.model_path))
+        .then(|| resolve_model_path(&app, &config.tags_path));
+    let catalog = model_manager::load_catalog(&app)?;
+
+    let info = tauri::async_runtime::spawn_blocking(move || {
+        inventory::inspect(&models_dir, &path, tags_path.as_deref(), &catalog)
+    })
+    .await
+    .map_err(|e| AppError::Internal(e.to_string()))??;
+    Ok(info)
+}
+
+/// Deletes an installed model and returns the number of bytes freed. The active
+/// model cannot be deleted.
+#[tauri::command]
+pub async fn delete_model(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    path_str: String,
+) -> Result<u64, AppError> {
+    let config = get_config(state)?;
+    let path = resolve_model_path(&app, &path_str);
+    if path == resolve_model_path(&app, &config.model_path) {
+        return Err(AppError::InvalidRequest(
+            "The active model cannot be deleted; switch to another model first".to_string(),
+        ));
+    }
+    let models_dir = model_manager::models_dir(&app)?;
+    let freed = inventory::delete_model(&models_dir, &path)?;
+    app.state::<AppState>().forget_tagger(&path);
+    println!("Deleted model {:?} ({} bytes)", path, freed);
+    Ok(freed)
+}
+
+/// Imports a model for offline use: either a `.onnx` file together with `tags_path`,
+/// or a `.zip`/`.tar`/`.tar.gz` bundle with a manifest when `tags_path` is omitted.
+#[tauri::command]
+pub async fn import_model(
+    app: AppHandle,
+    path: String,
+    tags_path: Option<String>,
+) -> Result<ModelEntry, AppError> {
+    let entry = model_manager::import_model(
+        &app,
+        std::path::PathBuf::from(path),
+        tags_path.map(std::path::PathBuf::from),
+    )
+    .await?;
+    // A re-import replaces the files of an earlier one
+    app.state::<AppState>()
+        .forget_tagger(&resolve_model_path(&app, &entry.model_path));
+    println!("Imported model {} to {}", entry.id, entry.model_path);
+    Ok(entry)
+}
+
+#[tauri::command]
+pub fn cancel_download(app: AppHandle) {
+    model_manager::cancel_download(&app);
+}
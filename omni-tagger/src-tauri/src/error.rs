@@ -0,0 +1,317 @@
+use omni_tagger_settings::profiles::ProfileError;
+use omni_tagger_settings::settings::{ConfigError, FieldError};
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+use std::path::Path;
+
+/// Error returned by Tauri commands, the processing pipeline and the HTTP API.
+///
+/// Serialized as `{ "code": "ModelMissing", "message": "...", "details": {...} }` so
+/// the settings UI and the browser extension can react to specific failures.
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum AppError {
+    #[error("Model file not found at {path}")]
+    ModelMissing { path: String },
+
+    #[error("Failed to load model {path}: {message}")]
+    ModelLoadFailed { path: String, message: String },
+
+    #[error("Download failed: {0}")]
+    DownloadFailed(String),
+
+    #[error("Checksum mismatch for {path}: expected {expected}, got {actual}")]
+    ChecksumMismatch {
+        path: String,
+        expected: String,
+        actual: String,
+    },
+
+    #[error("Download cancelled")]
+    Cancelled,
+
+    #[error("Unsupported image: {0}")]
+    UnsupportedImage(String),
+
+    #[error("Invalid URL: {0}")]
+    InvalidUrl(String),
+
+    #[error("Blocked URL: {0}")]
+    SsrfBlocked(String),
+
+    #[error("Payload exceeds maximum allowed size of {limit} bytes")]
+    PayloadTooLarge { limit: u64 },
+
+    #[error("Inference failed: {0}")]
+    InferenceFailed(String),
+
+    /// Stdin or clipboard input was requested but holds no image.
+    #[error("{0}")]
+    InputUnavailable(String),
+
+    #[error("{0}")]
+    InvalidRequest(String),
+
+    #[error("Missing or invalid bearer token")]
+    Unauthorized,
+
+    /// An HTTP API request whose `Host` or `Origin` is not the local server, e.g. from
+    /// a web page using DNS rebinding.
+    #[error("Request refused: {0}")]
+    Forbidden(String),
+
+    #[error("Configuration error: {0}")]
+    Config(String),
+
+    /// Settings rejected by `set_config`; nothing was saved.
+    #[error("Invalid settings: {}", join_errors(.0))]
+    InvalidConfig(Vec<FieldError>),
+
+    #[error("Registration failed: {0}")]
+    Registration(String),
+
+    #[error("{0}")]
+    Io(String),
+
+    #[error("{0}")]
+    Internal(String),
+}
+
+impl AppError {
+    pub fn model_missing(path: &Path) -> Self {
+        Self::ModelMissing {
+            path: path.display().to_string(),
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::ModelMissing { .. } => "ModelMissing",
+            Self::ModelLoadFailed { .. } => "ModelLoadFailed",
+            Self::DownloadFailed(_) => "DownloadFailed",
+            Self::ChecksumMismatch { .. } => "ChecksumMismatch",
+            Self::Cancelled => "Cancelled",
+            Self::UnsupportedImage(_) => "UnsupportedImage",
+            Self::InvalidUrl(_) => "InvalidUrl",
+            Self::SsrfBlocked(_) => "SsrfBlocked",
+            Self::PayloadTooLarge { .. } => "PayloadTooLarge",
+            Self::InferenceFailed(_) => "InferenceFailed",
+            Self::InputUnavailable(_) => "InputUnavailable",
+            Self::InvalidRequest(_) => "InvalidRequest",
+            Self::Unauthorized => "Unauthorized",
+            Self::Forbidden(_) => "Forbidden",
+            Self::Config(_) => "Config",
+            Self::InvalidConfig(_) => "InvalidConfig",
+            Self::Registration(_) => "Registration",
+            Self::Io(_) => "Io",
+            Self::Internal(_) => "Internal",
+        }
+    }
+
+    /// Machine readable data the frontend needs to act on the error.
+    pub fn details(&self) -> serde_json::Value {
+        match self {
+            Self::ModelMissing { path } | Self::ModelLoadFailed { path, .. } => {
+                serde_json::json!({ "path": path })
+            }
+            Self::ChecksumMismatch {
+                path,
+                expected,
+                actual,
+            } => serde_json::json!({ "path": path, "expected": expected, "actual": actual }),
+            Self::PayloadTooLarge { limit } => serde_json::json!({ "limit": limit }),
+            Self::InvalidConfig(errors) => serde_json::json!({ "errors": errors }),
+            _ => serde_json::Value::Null,
+        }
+    }
+}
+
+fn join_errors(errors: &[FieldError]) -> String {
+    errors
+        .iter()
+        .map(FieldError::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+impl Serialize for AppError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut s = serializer.serialize_struct("AppError", 3)?;
+        s.serialize_field("code", self.code())?;
+        s.serialize_field("message", &self.to_string())?;
+        s.serialize_field("details", &self.details())?;
+        s.end()
+    }
+}
+
+impl From<&omni_tagger_core::Error> for AppError {
+    fn from(err: &omni_tagger_core::Error) -> Self {
+        use omni_tagger_core::Error;
+
+        match err {
+            Error::TagsFile { path, message } | Error::ModelLoad { path, message } => {
+                if path.exists() {
+                    Self::ModelLoadFailed {
+                        path: path.display().to_string(),
+                        message: message.clone(),
+                    }
+                } else {
+                    Self::ModelMissing {
+                        path: path.display().to_string(),
+                    }
+                }
+            }
+            Error::Inference(message) => Self::InferenceFailed(message.clone()),
+            Error::ImageDecode(e) => Self::UnsupportedImage(e.to_string()),
+            Error::UnsupportedFormat(message) => Self::UnsupportedImage(message.clone()),
+            Error::ImageTooLarge(_) => Self::UnsupportedImage(err.to_string()),
+            Error::InvalidUrl(message) => Self::InvalidUrl(message.clone()),
+            Error::BlockedUrl(message) => Self::SsrfBlocked(message.clone()),
+            Error::PayloadTooLarge { limit } => Self::PayloadTooLarge { limit: *limit },
+            Error::EmptyInput => Self::InputUnavailable(err.to_string()),
+            Error::ChecksumMismatch {
+                path,
+                expected,
+                actual,
+            } => Self::ChecksumMismatch {
+                path: path.display().to_string(),
+                expected: expected.clone(),
+                actual: actual.clone(),
+            },
+            Error::InvalidRepo(message) | Error::InvalidBundle(message) => {
+                Self::InvalidRequest(message.clone())
+            }
+            Error::Catalog(message) | Error::Network(message) => Self::Config(message.clone()),
+            Error::IncompleteDownload { .. } | Error::Timeout { .. } => {
+                Self::DownloadFailed(err.to_string())
+            }
+            Error::Cancelled => Self::Cancelled,
+            Error::Http(e) => Self::DownloadFailed(e.to_string()),
+            Error::Io { .. } => Self::Io(err.to_string()),
+        }
+    }
+}
+
+impl From<omni_tagger_core::Error> for AppError {
+    fn from(err: omni_tagger_core::Error) -> Self {
+        Self::from(&err)
+    }
+}
+
+impl From<&ProfileError> for AppError {
+    fn from(err: &ProfileError) -> Self {
+        match err {
+            ProfileError::InvalidName(_)
+            | ProfileError::ReservedName(_)
+            | ProfileError::NameConflict { .. }
+            | ProfileError::NotFound(_) => {
+                Self::InvalidRequest(err.to_string())
+            }
+            ProfileError::Invalid { .. } => Self::Config(err.to_string()),
+            ProfileError::Io { .. } => Self::Io(err.to_string()),
+        }
+    }
+}
+
+impl From<ProfileError> for AppError {
+    fn from(err: ProfileError) -> Self {
+        Self::from(&err)
+    }
+}
+
+impl From<ConfigError> for AppError {
+    fn from(err: ConfigError) -> Self {
+        match err {
+            ConfigError::ExclusionFile { .. } => Self::Io(err.to_string()),
+            _ => Self::Config(err.to_string()),
+        }
+    }
+}
+
+impl From<anyhow::Error> for AppError {
+    /// Classifies an error chain by the first typed cause found in it; anything else
+    /// is reported as `Internal` with the full context chain as the message.
+    fn from(err: anyhow::Error) -> Self {
+        for cause in err.chain() {
+            if let Some(e) = cause.downcast_ref::<AppError>() {
+                return e.clone();
+            }
+            if let Some(e) = cause.downcast_ref::<omni_tagger_core::Error>() {
+                return Self::from(e);
+            }
+            if let Some(e) = cause.downcast_ref::<ProfileError>() {
+                return Self::from(e);
+            }
+            if let Some(e) = cause.downcast_ref::<image::ImageError>() {
+                return Self::UnsupportedImage(e.to_string());
+            }
+        }
+        Self::Internal(format!("{:#}", err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn test_serialize_code_and_details() -> Result<(), Box<dyn std::error::Error>> {
+        let err = AppError::ModelMissing {
+            path: "/models/model.onnx".to_string(),
+        };
+        let value = serde_json::to_value(&err)?;
+        assert_eq!(value["code"], "ModelMissing");
+        assert_eq!(value["details"]["path"], "/models/model.onnx");
+        assert_eq!(
+            value["message"],
+            "Model file not found at /models/model.onnx"
+        );
+
+        let value = serde_json::to_value(AppError::InferenceFailed("boom".into()))?;
+        assert_eq!(value["code"], "InferenceFailed");
+        assert!(value["details"].is_null());
+
+        let err = AppError::InvalidConfig(vec![
+            FieldError::new("threshold", "must be between 0 and 1"),
+            FieldError::new("preprocessing.input_size", "must be greater than 0"),
+        ]);
+        let value = serde_json::to_value(&err)?;
+        assert_eq!(value["code"], "InvalidConfig");
+        assert_eq!(value["details"]["errors"][1]["field"], "preprocessing.input_size");
+        assert_eq!(
+            value["message"],
+            "Invalid settings: threshold: must be between 0 and 1; \
+             preprocessing.input_size: must be greater than 0"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_core_error() {
+        let err = AppError::from(omni_tagger_core::Error::BlockedUrl("127.0.0.1".into()));
+        assert_eq!(err.code(), "SsrfBlocked");
+
+        let err = AppError::from(omni_tagger_core::Error::ModelLoad {
+            path: std::env::temp_dir().join("omni_tagger_missing_model.onnx"),
+            message: "not found".into(),
+        });
+        assert_eq!(err.code(), "ModelMissing");
+    }
+
+    #[test]
+    fn test_from_anyhow_keeps_typed_cause() {
+        let err = Err::<(), _>(omni_tagger_core::Error::PayloadTooLarge { limit: 10 })
+            .context("Failed to fetch image")
+            .unwrap_err();
+        assert_eq!(AppError::from(err), AppError::PayloadTooLarge { limit: 10 });
+
+        let err = anyhow::Error::from(AppError::InputUnavailable("empty".into()))
+            .context("Failed to read clipboard");
+        assert_eq!(AppError::from(err).code(), "InputUnavailable");
+
+        let err = anyhow::anyhow!("inner").context("outer");
+        assert_eq!(
+            AppError::from(err),
+            AppError::Internal("outer: inner".into())
+        );
+    }
+}
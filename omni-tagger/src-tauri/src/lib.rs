@@ -1,56 +1,175 @@
 mod config;
+mod error;
 mod model_manager;
 mod processor;
 mod registry;
+mod server;
 mod state;
-mod tagger;
+mod watcher;
 
-use crate::config::{load_config, resolve_model_path, AppConfig};
-use crate::processor::process_inputs;
-use crate::state::AppState;
-use crate::tagger::Tagger;
+use crate::config::{load_config, resolve_model_path, save_config, AppConfig};
+use crate::processor::{needs_own_process, process_inputs, wants_stdin};
+use crate::state::{AppState, Job};
+use omni_tagger_core::{decode, Tagger};
+use omni_tagger_settings::profiles;
 use std::sync::Mutex;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use tauri::{
-    menu::{Menu, MenuItem},
+    menu::{CheckMenuItem, Menu, MenuItem, Submenu},
     tray::TrayIconBuilder,
-    Emitter, Manager,
+    AppHandle, Emitter, Manager, Wry,
 };
 
+const TRAY_ID: &str = "main";
+
+/// Prefix of the tray menu ids that switch to a profile, followed by its name.
+const PROFILE_MENU_PREFIX: &str = "profile:";
+
+/// Builds the tray menu from the current settings and profiles.
+fn tray_menu<M: Manager<Wry>>(app: &M) -> tauri::Result<Menu<Wry>> {
+    let config = app
+        .state::<AppState>()
+        .config
+        .lock()
+        .expect("failed to lock config")
+        .clone();
+    let profiles = match config::profiles_dir(app.app_handle()) {
+        Ok(dir) => profiles::list(&dir).unwrap_or_else(|e| {
+            eprintln!("Failed to list profiles: {}", e);
+            Vec::new()
+        }),
+        Err(_) => Vec::new(),
+    };
+
+    let quit_i = MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?;
+    let settings_i = MenuItem::with_id(app, "settings", "Settings", true, None::<&str>)?;
+    let clipboard_i = MenuItem::with_id(
+        app,
+        "tag_clipboard",
+        "Tag Clipboard Image",
+        true,
+        None::<&str>,
+    )?;
+    let watch_i = CheckMenuItem::with_id(
+        app,
+        "watch_folders",
+        "Watch Folders",
+        true,
+        config.watch_enabled,
+        None::<&str>,
+    )?;
+
+    let profiles_i = Submenu::with_id(app, "profiles", "Profiles", !profiles.is_empty())?;
+    for name in &profiles {
+        let active = config.active_profile.as_deref() == Some(name.as_str());
+        profiles_i.append(&CheckMenuItem::with_id(
+            app,
+            format!("{}{}", PROFILE_MENU_PREFIX, name),
+            name,
+            true,
+            active,
+            None::<&str>,
+        )?)?;
+    }
+
+    Menu::with_items(
+        app,
+        &[&clipboard_i, &watch_i, &profiles_i, &settings_i, &quit_i],
+    )
+}
+
+/// Rebuilds the tray menu after the profiles or the active profile changed.
+pub(crate) fn refresh_tray_menu(app: &AppHandle) -> tauri::Result<()> {
+    if let Some(tray) = app.tray_by_id(TRAY_ID) {
+        tray.set_menu(Some(tray_menu(app)?))?;
+    }
+    Ok(())
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<Vec<String>>();
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<Job>();
     let active_tasks = Arc::new(AtomicUsize::new(0));
     let active_tasks_clone = Arc::clone(&active_tasks);
 
-    tauri::Builder::default()
+    // Standard input, standard output and the environment belong to this process, so
+    // such runs are processed here even when another instance is running; forwarding
+    // the arguments would leave the piped data and OMNI_TAGGER_* variables behind and
+    // print the tags to the other instance's output.
+    let args: Vec<String> = std::env::args().collect();
+    let own_process = needs_own_process(&args);
+
+    // Drain piped image data up front
+    let stdin_image = if wants_stdin(&args) {
+        match decode::read_input(std::io::stdin().lock()) {
+            Ok(bytes) => Some(bytes),
+            Err(e) => {
+                eprintln!("Failed to read image data from standard input: {}", e);
+                std::process::exit(1);
+            }
+        }
+    } else {
+        None
+    };
+
+    let builder = tauri::Builder::default()
         .manage(AppState {
             tagger: Mutex::new(None),
+            other_tagger: Mutex::new(None),
             config: Mutex::new(AppConfig::default()),
+            config_reset: Mutex::new(None),
             download_lock: tokio::sync::Mutex::new(()),
+            download_cancel: Mutex::new(tokio_util::sync::CancellationToken::new()),
             input_tx: tx,
             active_tasks,
+            stdin_image: Mutex::new(stdin_image),
         })
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_dialog::init())
-        .plugin(tauri_plugin_notification::init())
-        .plugin(tauri_plugin_single_instance::init(move |app, argv, _cwd| {
+        .plugin(tauri_plugin_notification::init());
+    let builder = if own_process {
+        builder
+    } else {
+        builder.plugin(tauri_plugin_single_instance::init(move |app, argv, cwd| {
             println!("Single Instance: {:?}", argv);
             let state = app.state::<AppState>();
             state.active_tasks.fetch_add(1, Ordering::SeqCst);
-            let _ = state.input_tx.send(argv);
+            // Relative paths belong to the forwarding process; its environment does
+            // not reach us (runs with overrides in it are never forwarded)
+            let _ = state.input_tx.send(Job {
+                cwd: (!cwd.is_empty()).then(|| cwd.into()),
+                ..Job::from(argv)
+            });
         }))
+    };
+
+    builder
         .setup(|app| {
-            let quit_i = MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?;
-            let settings_i = MenuItem::with_id(app, "settings", "Settings", true, None::<&str>)?;
-            let menu = Menu::with_items(app, &[&settings_i, &quit_i])?;
+            let (config, reset) = load_config(app.handle());
+            *app.state::<AppState>().config.lock().expect("failed to lock config") = config.clone();
+            if let Some(reset) = reset {
+                use tauri_plugin_notification::NotificationExt;
+                let _ = app
+                    .notification()
+                    .builder()
+                    .title("Settings reset")
+                    .body(format!(
+                        "Your settings could not be loaded and were reset to defaults. {}",
+                        reset.reason
+                    ))
+                    .show();
+                let _ = app.emit("config-reset", &reset);
+                let state = app.state::<AppState>();
+                *state.config_reset.lock().expect("failed to lock config") = Some(reset);
+            }
 
-            let _tray = TrayIconBuilder::new()
+            let menu = tray_menu(app)?;
+            let _tray = TrayIconBuilder::with_id(TRAY_ID)
                 .menu(&menu)
                 .show_menu_on_left_click(false)
-                .on_menu_event(|app, event| match event.id.as_ref() {
+                .on_menu_event(move |app, event| match event.id.as_ref() {
                     "quit" => {
                         app.exit(0);
                     }
@@ -60,20 +179,63 @@ pub fn run() {
                             let _ = window.set_focus();
                         }
                     }
-                    _ => {}
+                    "tag_clipboard" => {
+                        let state = app.state::<AppState>();
+                        state.active_tasks.fetch_add(1, Ordering::SeqCst);
+                        let args = vec!["omni-tagger".to_string(), "--from-clipboard".to_string()];
+                        let _ = state.input_tx.send(args.into());
+                    }
+                    "watch_folders" => {
+                        let state = app.state::<AppState>();
+                        let mut config = state.config.lock().expect("failed to lock config");
+                        config.watch_enabled = !config.watch_enabled;
+                        if let Err(e) = save_config(app, &config) {
+                            eprintln!("Failed to save config: {}", e);
+                        }
+                        drop(config);
+                        if let Err(e) = refresh_tray_menu(app) {
+                            eprintln!("Failed to update tray menu: {}", e);
+                        }
+                        let _ = app.emit("config-changed", ());
+                    }
+                    id => {
+                        if let Some(name) = id.strip_prefix(PROFILE_MENU_PREFIX) {
+                            // Loading another model takes a while; keep the menu responsive
+                            let app = app.clone();
+                            let name = name.to_string();
+                            tauri::async_runtime::spawn(async move {
+                                if let Err(e) = config::switch_to_profile(&app, &name) {
+                                    eprintln!("Failed to switch to profile {:?}: {}", name, e);
+                                    use tauri_plugin_notification::NotificationExt;
+                                    let _ = app
+                                        .notification()
+                                        .builder()
+                                        .title("Error")
+                                        .body(format!("Failed to switch profile: {}", e))
+                                        .show();
+                                    let _ = refresh_tray_menu(&app);
+                                }
+                            });
+                        }
+                    }
                 })
                 .build(app)?;
 
-            let config = load_config(app.handle());
-            *app.state::<AppState>().config.lock().expect("failed to lock config") = config.clone();
             let app_handle = app.handle().clone();
 
             // Setup background worker for queue processing
             let app_handle_worker = app_handle.clone();
             tauri::async_runtime::spawn(async move {
-                while let Some(args) = rx.recv().await {
-                    if let Err(e) = process_inputs(&app_handle_worker, args).await {
+                while let Some(mut job) = rx.recv().await {
+                    let done = job.done.take();
+                    let result = process_inputs(&app_handle_worker, job).await;
+                    if let Some(done) = done {
+                        let _ = done.send(result.is_ok());
+                    }
+                    if let Err(e) = result {
                         eprintln!("Error processing inputs: {}", e);
+                        // Lets an open settings window react to the error code (e.g. offer a download)
+                        let _ = app_handle_worker.emit("processing-error", &e);
                         use tauri_plugin_notification::NotificationExt;
                         let _ = app_handle_worker
                             .notification()
@@ -102,7 +264,10 @@ pub fn run() {
             if args.len() > 1 {
                 let state = app.state::<AppState>();
                 state.active_tasks.fetch_add(1, Ordering::SeqCst);
-                let _ = state.input_tx.send(args);
+                let _ = state.input_tx.send(Job {
+                    read_env: true,
+                    ..Job::from(args)
+                });
                 return Ok(());
             }
 
@@ -110,6 +275,12 @@ pub fn run() {
                 let _ = window.show();
             }
 
+            watcher::start(app.handle().clone());
+
+            if config.api_server.enabled {
+                server::start(app.handle().clone(), config.api_server.clone());
+            }
+
             // Preload Tagger in background for GUI usage
             let model_path_str = config.model_path.clone();
             let tags_path_str = config.tags_path.clone();
@@ -157,8 +328,24 @@ pub fn run() {
         .invoke_handler(tauri::generate_handler![
             config::get_config,
             config::set_config,
+            config::get_config_reset,
+            config::list_profiles,
+            config::save_profile,
+            config::switch_profile,
+            config::delete_profile,
+            config::export_config,
+            config::import_config,
             config::check_model_exists,
             config::download_new_model,
+            config::get_model_catalog,
+            config::download_catalog_model,
+            config::download_hub_model,
+            config::cancel_download,
+            config::list_installed_models,
+            config::inspect_model,
+            config::delete_model,
+            config::import_model,
+            config::verify_models,
             registry::register_context_menu,
             registry::register_native_host,
             registry::unregister_native_host
@@ -1,40 +1,17 @@
-use anyhow::{anyhow, Context, Result};
-use futures_util::StreamExt;
-
-use std::path::Path;
-use tauri::{AppHandle, Emitter};
-use tokio::fs::File;
-use tokio::io::AsyncWriteExt;
-
-const SWINV2_MODEL_URL: &str =
-    "https://huggingface.co/SmilingWolf/wd-v1-4-swinv2-tagger-v2/resolve/main/model.onnx";
-const CONVNEXT_MODEL_URL: &str =
-    "https://huggingface.co/SmilingWolf/wd-v1-4-convnext-tagger-v2/resolve/main/model.onnx";
-const CONVNEXTV2_MODEL_URL: &str =
-    "https://huggingface.co/SmilingWolf/wd-v1-4-convnextv2-tagger-v2/resolve/main/model.onnx";
-
-const TAGS_URL: &str =
-    "https://huggingface.co/SmilingWolf/wd-v1-4-swinv2-tagger-v2/resolve/main/selected_tags.csv";
-
-#[derive(Clone, serde::Serialize)]
-struct DownloadProgress {
-    file: String,
-    total: u64,
-    downloaded: u64,
-    percent: f64,
-}
-
-fn get_model_url(path: &Path) -> Option<&'static str> {
-    let file_name = path.file_name()?.to_str()?;
-    match file_name {
-        "model.onnx" => Some(SWINV2_MODEL_URL),
-        "convnext.onnx" => Some(CONVNEXT_MODEL_URL),
-        "convnextv2.onnx" => Some(CONVNEXTV2_MODEL_URL),
-        _ => None,
-    }
-}
+use anyhow::Result;
+use omni_tagger_core::checksum::ChecksumStatus;
+use omni_tagger_core::bundle;
+use omni_tagger_core::catalog::{Catalog, ModelEntry};
+use omni_tagger_core::download::{download_with_retry, DownloadProgress, RetryPolicy};
+use omni_tagger_core::inventory::{self, ModelInfo};
+use serde::Serialize;
+use tokio_util::sync::CancellationToken;
+
+use std::path::{Path, PathBuf};
+use tauri::{path::BaseDirectory, AppHandle, Emitter};
 
 use tauri::Manager;
+use crate::error::AppError;
 use crate::state::AppState;
 
 pub async fn check_and_download_models(
@@ -46,76 +23,256 @@ pub async fn check_and_download_models(
     let state = app.state::<AppState>();
     let _lock = state.download_lock.lock().await;
 
+    let catalog = load_catalog(app)?;
+    let mut missing = Vec::new();
     if !model_path.exists() {
-        if let Some(url) = get_model_url(model_path) {
-            download_file(app, url, model_path).await?;
+        // Imported models have no URL to download them from again
+        let entry = catalog
+            .find_by_model_path(model_path)
+            .filter(|e| !e.model_url.is_empty());
+        if let Some(entry) = entry {
+            missing.push(DownloadJob {
+                url: &entry.model_url,
+                dest: model_path,
+                sha256: entry.sha256.as_deref(),
+            });
         } else {
-            return Err(anyhow!("Model file not found at {:?} and cannot be automatically downloaded. Please ensure the path is correct or download the model manually.", model_path));
+            return Err(AppError::model_missing(model_path).into());
         }
     }
 
     if !tags_path.exists() {
-        download_file(app, TAGS_URL, tags_path).await?;
+        let entry = catalog
+            .find_by_tags_path(tags_path)
+            .filter(|e| !e.tags_url.is_empty());
+        if let Some(entry) = entry {
+            missing.push(DownloadJob {
+                url: &entry.tags_url,
+                dest: tags_path,
+                sha256: entry.tags_sha256.as_deref(),
+            });
+        } else {
+            return Err(AppError::model_missing(tags_path).into());
+        }
     }
 
+    download_files(app, &missing).await?;
+
     // Emit finished event
     let _ = app.emit("model-download-finished", ());
 
     Ok(())
 }
 
-pub fn check_file_exists(path: &Path) -> bool {
-    path.exists()
+/// User additions to the bundled model catalog, next to `config.json`.
+pub fn user_catalog_path(app: &AppHandle) -> Result<PathBuf> {
+    Ok(app.path().resolve("models.json", BaseDirectory::AppConfig)?)
 }
 
-pub async fn download_file(app: &AppHandle, url: &str, dest: &Path) -> Result<()> {
-    let client = reqwest::Client::new();
-    let res = client.get(url).send().await.context("Failed to connect")?;
+pub fn load_catalog(app: &AppHandle) -> Result<Catalog> {
+    Ok(Catalog::load(&user_catalog_path(app)?)?)
+}
 
-    let total_size = res.content_length().unwrap_or(0);
+/// Downloads the model of a catalog entry and, if missing, its tags file.
+pub async fn download_catalog_model(
+    app: &AppHandle,
+    entry: &ModelEntry,
+    model_path: &Path,
+    tags_path: &Path,
+) -> Result<()> {
+    let _lock = app.state::<AppState>().download_lock.lock().await;
 
-    // Create parent directory if it doesn't exist
-    if let Some(parent) = dest.parent() {
-        tokio::fs::create_dir_all(parent)
-            .await
-            .context("Failed to create directory")?;
+    let mut jobs = vec![DownloadJob {
+        url: &entry.model_url,
+        dest: model_path,
+        sha256: entry.sha256.as_deref(),
+    }];
+    if !tags_path.exists() {
+        jobs.push(DownloadJob {
+            url: &entry.tags_url,
+            dest: tags_path,
+            sha256: entry.tags_sha256.as_deref(),
+        });
     }
+    download_files(app, &jobs).await
+}
+
+/// Installs a local model + tags pair or an offline bundle and adds it to the user
+/// catalog. Runs on a blocking thread since it hashes and copies the model.
+pub async fn import_model(
+    app: &AppHandle,
+    source: PathBuf,
+    tags: Option<PathBuf>,
+) -> Result<ModelEntry> {
+    let models_dir = models_dir(app)?;
+    let entry = tauri::async_runtime::spawn_blocking(move || match tags {
+        Some(tags) => bundle::import_files(&models_dir, &source, &tags),
+        None => bundle::import_bundle(&models_dir, &source),
+    })
+    .await??;
+    Catalog::register(&user_catalog_path(app)?, entry.clone())?;
+    Ok(entry)
+}
+
+/// Directory under AppLocalData that holds downloaded and imported models.
+pub fn models_dir(app: &AppHandle) -> Result<PathBuf> {
+    Ok(app.path().resolve("models", BaseDirectory::AppLocalData)?)
+}
+
+/// Installed models and the space the models directory takes up.
+#[derive(Debug, Serialize)]
+pub struct InstalledModels {
+    pub models: Vec<ModelInfo>,
+    /// Bytes used by the models directory, including tags files and partial downloads.
+    pub disk_usage: u64,
+}
+
+/// Inspects every model under `models_dir`. `active` is the configured model and
+/// tags file, whose tags may live somewhere the naming conventions do not cover.
+pub fn list_installed(
+    models_dir: &Path,
+    active: (&Path, &Path),
+    catalog: &Catalog,
+) -> Result<InstalledModels> {
+    let (active_model, active_tags) = active;
+    let models = inventory::find_models(models_dir)
+        .iter()
+        .map(|model| {
+            let tags = (model == active_model).then_some(active_tags);
+            Ok(inventory::inspect(models_dir, model, tags, catalog)?)
+        })
+        .collect::<Result<_>>()?;
+    Ok(InstalledModels {
+        models,
+        disk_usage: inventory::disk_usage(models_dir),
+    })
+}
+
+pub fn check_file_exists(path: &Path) -> bool {
+    path.exists()
+}
+
+/// Progress of one file within a batch, emitted as `model-download-progress`.
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchProgress {
+    #[serde(flatten)]
+    pub progress: DownloadProgress,
+    /// Zero-based position of the current file in the batch.
+    pub file_index: usize,
+    pub file_count: usize,
+    /// Completion of the whole batch, counting every file equally.
+    pub overall_percent: f64,
+}
 
-    let mut file = File::create(dest).await.context("Failed to create file")?;
-    let mut stream = res.bytes_stream();
-    let mut downloaded: u64 = 0;
-
-    let filename = dest
-        .file_name()
-        .and_then(|s| s.to_str())
-        .unwrap_or("unknown")
-        .to_string();
-
-    while let Some(item) = stream.next().await {
-        let chunk = item.context("Error while downloading")?;
-        file.write_all(&chunk)
-            .await
-            .context("Error while writing to file")?;
-
-        downloaded += chunk.len() as u64;
-
-        if total_size > 0 {
-            let percent = (downloaded as f64 / total_size as f64) * 100.0;
-            let _ = app.emit(
-                "model-download-progress",
-                DownloadProgress {
-                    file: filename.clone(),
-                    total: total_size,
-                    downloaded,
-                    percent,
-                },
-            );
+impl BatchProgress {
+    fn new(progress: &DownloadProgress, file_index: usize, file_count: usize) -> Self {
+        let current = if progress.is_indeterminate() {
+            0.0
+        } else {
+            progress.percent / 100.0
+        };
+        Self {
+            progress: progress.clone(),
+            file_index,
+            file_count,
+            overall_percent: (file_index as f64 + current) / file_count.max(1) as f64 * 100.0,
         }
     }
+}
+
+/// Returns the token for the next download, replacing it if the last one was cancelled.
+fn download_token(app: &AppHandle) -> CancellationToken {
+    let state = app.state::<AppState>();
+    let mut token = state
+        .download_cancel
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    if token.is_cancelled() {
+        *token = CancellationToken::new();
+    }
+    token.clone()
+}
+
+/// Aborts the running download; its partial file is kept so it can be resumed.
+pub fn cancel_download(app: &AppHandle) {
+    let state = app.state::<AppState>();
+    state
+        .download_cancel
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .cancel();
+}
+
+/// A file to fetch as part of a batch.
+pub struct DownloadJob<'a> {
+    pub url: &'a str,
+    pub dest: &'a Path,
+    /// Expected SHA-256 from the catalog, if known.
+    pub sha256: Option<&'a str>,
+}
 
+pub async fn download_file(app: &AppHandle, url: &str, dest: &Path) -> Result<()> {
+    download_files(
+        app,
+        &[DownloadJob {
+            url,
+            dest,
+            sha256: None,
+        }],
+    )
+    .await
+}
+
+/// Downloads `files` one after another. Files of unknown size report through
+/// `model-download-indeterminate` since no percentage can be shown for them.
+pub async fn download_files(app: &AppHandle, files: &[DownloadJob<'_>]) -> Result<()> {
+    let cancel = download_token(app);
+    let policy = RetryPolicy::default();
+    let network = crate::config::get_config(app.state::<AppState>())?.network;
+    for (index, job) in files.iter().enumerate() {
+        download_with_retry(
+            job.url,
+            job.dest,
+            job.sha256,
+            &network,
+            &policy,
+            &cancel,
+            |progress| {
+                let event = if progress.is_indeterminate() {
+                    "model-download-indeterminate"
+                } else {
+                    "model-download-progress"
+                };
+                let _ = app.emit(event, BatchProgress::new(progress, index, files.len()));
+            },
+        )
+        .await?;
+    }
     Ok(())
 }
 
+/// Outcome of `verify_models` for a single file.
+#[derive(Debug, Serialize)]
+pub struct ModelCheck {
+    pub path: String,
+    #[serde(flatten)]
+    pub status: ChecksumStatus,
+}
+
+/// Hashes every file in `paths` and compares it with the checksum the catalog pins
+/// for it or, if there is none, the one recorded at download time.
+pub fn verify_files(paths: &[PathBuf], catalog: &Catalog) -> Result<Vec<ModelCheck>> {
+    paths
+        .iter()
+        .map(|path| {
+            Ok(ModelCheck {
+                path: path.display().to_string(),
+                status: inventory::checksum_status(path, catalog)?,
+            })
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -143,20 +300,31 @@ mod tests {
     }
 
     #[test]
-    fn test_get_model_url() {
-        assert_eq!(
-            get_model_url(Path::new("models/model.onnx")),
-            Some(SWINV2_MODEL_URL)
-        );
-        assert_eq!(
-            get_model_url(Path::new("/abs/path/to/convnext.onnx")),
-            Some(CONVNEXT_MODEL_URL)
-        );
-        assert_eq!(
-            get_model_url(Path::new("convnextv2.onnx")),
-            Some(CONVNEXTV2_MODEL_URL)
-        );
-        assert_eq!(get_model_url(Path::new("custom_model.onnx")), None);
-        assert_eq!(get_model_url(Path::new("some/other/file.txt")), None);
+    fn test_batch_progress_overall_percent() -> Result<(), Box<dyn std::error::Error>> {
+        let progress = DownloadProgress {
+            file: "tags.csv".to_string(),
+            total: 200,
+            downloaded: 100,
+            percent: 50.0,
+            bytes_per_sec: 10.0,
+            eta_secs: Some(10.0),
+        };
+        let batch = BatchProgress::new(&progress, 1, 2);
+        assert_eq!(batch.overall_percent, 75.0);
+
+        let value = serde_json::to_value(&batch)?;
+        assert_eq!(value["file"], "tags.csv");
+        assert_eq!(value["file_count"], 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_files_reports_status() -> Result<(), Box<dyn std::error::Error>> {
+        let path = std::env::temp_dir().join("omni_tagger_test_verify_missing.onnx");
+        let checks = verify_files(&[path.clone()], &Catalog::builtin())?;
+        let value = serde_json::to_value(&checks)?;
+        assert_eq!(value[0]["path"], path.display().to_string());
+        assert_eq!(value[0]["status"], "missing");
+        Ok(())
     }
 }
@@ -1,220 +1,481 @@
-use crate::config::{get_config, resolve_model_path};
+use crate::config::{self, get_config, resolve_model_path, AppConfig};
+use crate::error::AppError;
 use crate::model_manager;
-use crate::state::AppState;
-use crate::tagger::Tagger;
+use crate::state::{AppState, Job, TaggerKey};
 use anyhow::{Context, Result};
-use std::path::PathBuf;
+use clap::parser::ValueSource;
+use clap::{CommandFactory, FromArgMatches, Parser};
+use omni_tagger_core::{decode, fetch, inventory, postprocess_tags, Tagger};
+use omni_tagger_settings::overrides::ConfigOverrides;
+use omni_tagger_settings::profiles;
+use std::ffi::{OsStr, OsString};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
 use tauri::{AppHandle, Manager};
 use tauri_plugin_notification::NotificationExt;
 
-pub async fn process_inputs(app: &AppHandle, args: Vec<String>) -> Result<(), String> {
-    process_inputs_with_actions(
-        args,
-        |url| process_image_url(app, url),
-        |path| process_image_file(app, path),
-    )
-    .await
-    .map_err(|e| e.to_string())
+/// Where the generated tags are delivered.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum OutputTarget {
+    #[default]
+    Clipboard,
+    Stdout,
+    /// Append one JSON record per image to the given file (used by watch folders).
+    ResultsFile(PathBuf),
 }
 
-pub async fn process_inputs_with_actions<FUrl, FutUrl, FFile, FutFile>(
-    args: Vec<String>,
-    url_processor: FUrl,
-    file_processor: FFile,
-) -> Result<()>
-where
-    FUrl: FnOnce(String) -> FutUrl,
-    FutUrl: std::future::Future<Output = Result<()>>,
-    FFile: FnOnce(PathBuf) -> FutFile,
-    FutFile: std::future::Future<Output = Result<()>>,
-{
-    if args.len() <= 1 {
-        return Ok(());
-    }
+/// Command line of the desktop executable, either its own or forwarded to the
+/// running instance.
+#[derive(Parser, Debug)]
+#[command(name = "omni-tagger", version, about = "Tag an image and copy the tags to the clipboard")]
+struct InvocationArgs {
+    /// Image files ("-" reads image data from standard input); only the first is processed
+    inputs: Vec<String>,
 
-    let mut idx = 1;
-    let mut delete_after = false;
+    /// Download and tag the image at URL
+    #[arg(long, value_name = "URL")]
+    process_url: Option<String>,
 
-    if args.len() > idx && args[idx] == "--delete-after" {
-        delete_after = true;
-        idx += 1;
-    }
+    /// Tag the image on the clipboard
+    #[arg(long)]
+    from_clipboard: bool,
 
-    if args.len() <= idx {
-        return Ok(());
-    }
+    /// Read image data from standard input
+    #[arg(long)]
+    stdin: bool,
 
-    let arg = args[idx].clone();
+    /// Delete the input file after processing (for temporary files)
+    #[arg(long)]
+    delete_after: bool,
 
-    if arg == "--process-url" {
-        if args.len() > idx + 1 {
-            let url = args[idx + 1].clone();
-            url_processor(url).await?;
-        }
-    } else if !arg.starts_with("--") {
-        let path = PathBuf::from(arg);
-        let result = file_processor(path.clone()).await;
-
-        if delete_after {
-            if let Err(e) = std::fs::remove_file(&path) {
-                eprintln!("Failed to delete temp file {:?}: {}", path, e);
-            } else {
-                println!("Deleted temp file {:?}", path);
-            }
-        }
-        result?;
-    }
+    /// Print the tags instead of copying them to the clipboard
+    #[arg(long, conflicts_with = "results_file")]
+    stdout: bool,
 
-    Ok(())
+    /// Append one JSON record per image to FILE
+    #[arg(long, value_name = "FILE")]
+    results_file: Option<PathBuf>,
+
+    /// Use the tagging settings of a saved profile for this run
+    #[arg(long, value_name = "NAME")]
+    profile: Option<String>,
+
+    /// Use another config.json for this run
+    #[arg(long, value_name = "PATH")]
+    config: Option<PathBuf>,
+
+    #[command(flatten)]
+    overrides: ConfigOverrides,
 }
 
-async fn process_image_url(app: &AppHandle, url: String) -> Result<()> {
-    // Validate URL to prevent SSRF
-    let parsed_url = url::Url::parse(&url).context("Invalid URL format")?;
+impl InvocationArgs {
+    /// Parses `args`, reading the `OMNI_TAGGER_*` environment variables only if
+    /// `read_env` is set.
+    fn parse(args: &[String], read_env: bool) -> Result<Self, clap::Error> {
+        let mut command = Self::command();
+        if !read_env {
+            command = command.mut_args(|arg| arg.env(None::<&str>));
+        }
+        Self::from_arg_matches(&command.try_get_matches_from(args)?)
+    }
 
-    // 1. Check Scheme (only http/https)
-    let scheme = parsed_url.scheme();
-    if scheme != "http" && scheme != "https" {
-        return Err(anyhow::anyhow!("Invalid URL scheme. Only HTTP and HTTPS are allowed."));
+    /// The image to process. Context menus may pass several files; only the first
+    /// input is processed.
+    fn input(&self) -> Option<Input> {
+        if let Some(url) = &self.process_url {
+            Some(Input::Url(url.clone()))
+        } else if self.from_clipboard {
+            Some(Input::Source(ImageSource::Clipboard))
+        } else if self.stdin {
+            Some(Input::Source(ImageSource::Stdin))
+        } else {
+            self.inputs.first().map(|arg| {
+                if is_stdin_arg(arg) {
+                    Input::Source(ImageSource::Stdin)
+                } else {
+                    Input::File(PathBuf::from(arg))
+                }
+            })
+        }
     }
+}
 
-    // 2. Resolve and check Host (Reject localhost, loopback, private networks)
-    let host_str = parsed_url.host_str().ok_or_else(|| anyhow::anyhow!("URL has no host"))?;
+/// Options given on the command line that apply to a single request. None of them
+/// change the saved settings or the active profile.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct RunOptions {
+    pub output: OutputTarget,
+    /// Config file used instead of the app's `config.json`
+    pub config_path: Option<PathBuf>,
+    /// Profile whose tagging settings are applied
+    pub profile: Option<String>,
+    /// Flags and environment variables applied last
+    pub overrides: ConfigOverrides,
+}
 
-    // Simple blocklist for common local/private hostnames
-    let lower_host = host_str.to_lowercase();
-    if lower_host == "localhost" || lower_host.ends_with(".localhost") || lower_host == "broadcasthost" {
-        return Err(anyhow::anyhow!("URL resolves to a restricted local hostname"));
+impl RunOptions {
+    /// The settings this request runs with: the config file, then the profile, then
+    /// the overrides.
+    fn config(&self, app: &AppHandle) -> Result<AppConfig> {
+        let (config, profiles_dir) = match &self.config_path {
+            Some(path) => {
+                let dir = path.parent().unwrap_or(Path::new("."));
+                let mut config = config::read_config_file(path)?;
+                // Relative to that file rather than the app's config directory
+                config.exclusion_file = config
+                    .exclusion_file
+                    .map(|file| dir.join(file).to_string_lossy().into_owned());
+                (config, profiles::profiles_dir(dir))
+            }
+            None => (
+                get_config(app.state::<AppState>())?,
+                config::profiles_dir(app)?,
+            ),
+        };
+        let config = match &self.profile {
+            Some(name) => profiles::load(&profiles_dir, name)
+                .map_err(AppError::from)?
+                .apply(&config),
+            None => config,
+        };
+        let config = self.overrides.apply(&config);
+
+        let errors = config.validate();
+        if !errors.is_empty() {
+            return Err(AppError::InvalidConfig(errors).into());
+        }
+        Ok(config)
     }
+}
 
-    // Resolve IPs and check for private/loopback/unspecified
-    use std::net::ToSocketAddrs;
+#[derive(serde::Serialize)]
+struct TagRecord<'a> {
+    file: &'a str,
+    tags: &'a [String],
+    tagged_at: u64,
+}
 
-    // If it's an IP or a hostname, we try to resolve it to an IP address.
-    // We add a dummy port (80) because ToSocketAddrs requires it, even though we just want the IP.
-    let addr_str = format!("{}:80", host_str);
+/// Inputs that carry no path or URL and are read from the local machine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageSource {
+    Stdin,
+    Clipboard,
+}
 
-    // Note: This relies on the system DNS resolver.
-    if let Ok(mut addrs) = addr_str.to_socket_addrs() {
-        if let Some(addr) = addrs.next() {
-            let ip = addr.ip();
-            if ip.is_loopback() || ip.is_unspecified() || ip.is_multicast() {
-                 return Err(anyhow::anyhow!("URL resolves to a restricted IP address (loopback/unspecified/multicast)"));
-            }
+enum Input {
+    Url(String),
+    File(PathBuf),
+    Source(ImageSource),
+}
 
-            // Check for private IPv4
-            if let std::net::IpAddr::V4(ipv4) = ip {
-                if ipv4.is_private() || ipv4.is_link_local() {
-                     return Err(anyhow::anyhow!("URL resolves to a restricted private IPv4 address"));
-                }
+pub async fn process_inputs(app: &AppHandle, job: Job) -> Result<(), AppError> {
+    process_inputs_with_actions(
+        job,
+        |url, options| process_image_url(app, url, options),
+        |path, options| process_image_file(app, path, options),
+        |source, options| async move {
+            match source {
+                ImageSource::Stdin => process_image_stdin(app, options).await,
+                ImageSource::Clipboard => process_clipboard_image(app, options).await,
             }
-            // Check for private IPv6 (Unique Local Addresses fc00::/7)
-            if let std::net::IpAddr::V6(ipv6) = ip {
-                if (ipv6.segments()[0] & 0xfe00) == 0xfc00 {
-                     return Err(anyhow::anyhow!("URL resolves to a restricted private IPv6 address"));
+        },
+    )
+    .await
+    .map_err(AppError::from)
+}
+
+/// Runs the command line of `job`. Relative paths are resolved against `job.cwd`.
+pub async fn process_inputs_with_actions<FUrl, FutUrl, FFile, FutFile, FSource, FutSource>(
+    job: Job,
+    url_processor: FUrl,
+    file_processor: FFile,
+    source_processor: FSource,
+) -> Result<()>
+where
+    FUrl: FnOnce(String, RunOptions) -> FutUrl,
+    FutUrl: std::future::Future<Output = Result<()>>,
+    FFile: FnOnce(PathBuf, RunOptions) -> FutFile,
+    FutFile: std::future::Future<Output = Result<()>>,
+    FSource: FnOnce(ImageSource, RunOptions) -> FutSource,
+    FutSource: std::future::Future<Output = Result<()>>,
+{
+    let args = match InvocationArgs::parse(&job.args, job.read_env) {
+        Ok(args) => args,
+        Err(e) if !e.use_stderr() => {
+            // --help and --version
+            println!("{}", e);
+            return Ok(());
+        }
+        Err(e) => return Err(AppError::InvalidRequest(e.render().to_string()).into()),
+    };
+
+    let resolve = |path: PathBuf| match &job.cwd {
+        Some(cwd) => cwd.join(path),
+        None => path,
+    };
+    let output = match args.results_file.clone() {
+        Some(path) => OutputTarget::ResultsFile(resolve(path)),
+        None if args.stdout => OutputTarget::Stdout,
+        None => OutputTarget::Clipboard,
+    };
+    let input = match args.input() {
+        Some(Input::File(path)) => Some(Input::File(resolve(path))),
+        input => input,
+    };
+    let delete_after = args.delete_after;
+    let options = RunOptions {
+        output,
+        config_path: args.config.map(resolve),
+        profile: args.profile,
+        overrides: args.overrides,
+    };
+
+    match input {
+        Some(Input::Url(url)) => url_processor(url, options).await?,
+        Some(Input::Source(source)) => source_processor(source, options).await?,
+        Some(Input::File(path)) => {
+            let result = file_processor(path.clone(), options).await;
+
+            if delete_after {
+                if let Err(e) = std::fs::remove_file(&path) {
+                    eprintln!("Failed to delete temp file {:?}: {}", path, e);
+                } else {
+                    println!("Deleted temp file {:?}", path);
                 }
             }
+            result?;
         }
-    } else {
-        // If it can't resolve, reqwest will also fail, but we don't necessarily want to block it here
-        // if it's just a temporary DNS issue. However, for strict SSRF, failing closed is safer.
-        return Err(anyhow::anyhow!("Failed to resolve hostname for URL validation"));
+        None => {}
     }
 
-    // Download image
-    // Using reqwest with redirects disabled to prevent SSRF via redirect to localhost
-    let client = reqwest::Client::builder()
-        .redirect(reqwest::redirect::Policy::none())
-        .build()
-        .context("Failed to build client")?;
-    let mut resp = client
-        .get(&url)
-        .send()
-        .await
-        .context("Failed to send request")?;
-
-    // Check content length header if available
-    const MAX_DOWNLOAD_SIZE: u64 = 20 * 1024 * 1024; // 20 MB limit
-    if let Some(content_length) = resp.content_length() {
-        if content_length > MAX_DOWNLOAD_SIZE {
-            return Err(anyhow::anyhow!("File size {} exceeds maximum allowed size of {} bytes", content_length, MAX_DOWNLOAD_SIZE));
-        }
-    }
+    Ok(())
+}
 
-    // Stream download and enforce size limit manually
-    let mut bytes = Vec::new();
-    while let Some(chunk) = resp.chunk().await.context("Failed to get chunk")? {
-        if (bytes.len() + chunk.len()) as u64 > MAX_DOWNLOAD_SIZE {
-            return Err(anyhow::anyhow!("File size exceeds maximum allowed size of {} bytes", MAX_DOWNLOAD_SIZE));
-        }
-        bytes.extend_from_slice(&chunk);
-    }
+fn is_stdin_arg(arg: &str) -> bool {
+    arg == "-" || arg == "--stdin"
+}
 
-    let img = image::load_from_memory(&bytes).context("Failed to load image from URL")?;
+/// Returns true if the command line asks for the image to be read from standard input.
+/// A `-` that is the value of another option (e.g. `--profile -`) does not count.
+pub fn wants_stdin(args: &[String]) -> bool {
+    InvocationArgs::parse(args, false)
+        .is_ok_and(|args| matches!(args.input(), Some(Input::Source(ImageSource::Stdin))))
+}
 
-    run_inference_and_notify(app, img).await
+/// Returns true if the command line reads standard input, prints to standard output
+/// or is affected by `OMNI_TAGGER_*` environment variables. All of these belong to
+/// the launching process, so it has to do the work itself instead of forwarding the
+/// arguments to a running instance.
+pub fn needs_own_process(args: &[String]) -> bool {
+    needs_own_process_with_env(args, |name| std::env::var_os(name))
 }
 
-async fn process_image_file(app: &AppHandle, path: PathBuf) -> Result<()> {
-    let img = image::open(&path).context(format!("Failed to open image at {:?}", path))?;
-    run_inference_and_notify(app, img).await
+/// [`needs_own_process`] with the environment variables looked up through `env`.
+fn needs_own_process_with_env(args: &[String], env: impl Fn(&OsStr) -> Option<OsString>) -> bool {
+    let command = InvocationArgs::command();
+    let env_args: Vec<(String, OsString)> = command
+        .get_arguments()
+        .filter_map(|arg| Some((arg.get_id().to_string(), arg.get_env()?.to_os_string())))
+        .collect();
+    let Ok(matches) = command
+        .mut_args(|arg| arg.env(None::<&str>))
+        .try_get_matches_from(args)
+    else {
+        return false;
+    };
+    // A variable applies to every option that is not given on the command line
+    let env_overrides = env_args.iter().any(|(id, name)| {
+        env(name).is_some_and(|value| !value.is_empty())
+            && matches.value_source(id) != Some(ValueSource::CommandLine)
+    });
+    let Ok(parsed) = InvocationArgs::from_arg_matches(&matches) else {
+        return false;
+    };
+    parsed.stdout
+        || env_overrides
+        || matches!(parsed.input(), Some(Input::Source(ImageSource::Stdin)))
 }
 
-async fn run_inference_and_notify(app: &AppHandle, img: image::DynamicImage) -> Result<()> {
-    let state = app.state::<AppState>();
+async fn process_image_url(app: &AppHandle, url: String, options: RunOptions) -> Result<()> {
+    let config = options.config(app)?;
+    // The URL is validated against SSRF targets and the URL policy before anything is downloaded
+    let bytes = fetch::fetch_bytes(&url, &config.url_policy, &config.network).await?;
 
-    let config = get_config(state.clone()).map_err(|e| anyhow::anyhow!(e))?;
+    let img = decode::decode_image(&bytes).context("Failed to load image from URL")?;
 
-    // Quick check if loaded
-    let is_loaded = state
-        .tagger
+    run_inference_and_notify(app, img, &config, options.output, &url).await
+}
+
+async fn process_image_file(app: &AppHandle, path: PathBuf, options: RunOptions) -> Result<()> {
+    let config = options.config(app)?;
+    let img = decode::open_image(&path).context(format!("Failed to open image at {:?}", path))?;
+    run_inference_and_notify(app, img, &config, options.output, &path.to_string_lossy()).await
+}
+
+async fn process_image_stdin(app: &AppHandle, options: RunOptions) -> Result<()> {
+    let config = options.config(app)?;
+    // Standard input is drained at startup by the process that owns it; a running
+    // instance receiving forwarded arguments has no data to work with.
+    let bytes = app
+        .state::<AppState>()
+        .stdin_image
         .lock()
-        .map_err(|_| anyhow::anyhow!("Failed to lock tagger"))?
-        .is_some();
+        .map_err(|_| anyhow::anyhow!("Failed to lock stdin buffer"))?
+        .take()
+        .ok_or_else(|| AppError::InputUnavailable("No image data available from standard input".into()))?;
 
-    if !is_loaded {
-        // Load it now
-        let model_path = resolve_model_path(app, &config.model_path);
-        let tags_path = resolve_model_path(app, &config.tags_path);
+    let img = decode::decode_image(&bytes).context("Failed to load image from standard input")?;
+    run_inference_and_notify(app, img, &config, options.output, "-").await
+}
+
+async fn process_clipboard_image(app: &AppHandle, options: RunOptions) -> Result<()> {
+    let config = options.config(app)?;
+    let mut clipboard = arboard::Clipboard::new().context("Failed to access clipboard")?;
+    let data = clipboard
+        .get_image()
+        .map_err(|e| AppError::InputUnavailable(format!("Clipboard does not contain an image: {}", e)))?;
+
+    let img = image_from_rgba(data.width, data.height, data.bytes.into_owned())?;
+    run_inference_and_notify(app, img, &config, options.output, "clipboard").await
+}
+
+/// Wraps the raw RGBA buffer returned by `arboard` in a `DynamicImage`.
+fn image_from_rgba(width: usize, height: usize, bytes: Vec<u8>) -> Result<image::DynamicImage> {
+    // The bitmap is already decoded, so only its dimensions can be checked
+    let width = u32::try_from(width).unwrap_or(u32::MAX);
+    let height = u32::try_from(height).unwrap_or(u32::MAX);
+    decode::check_dimensions(width, height)?;
+    let buffer = image::RgbaImage::from_raw(width, height, bytes)
+        .ok_or_else(|| AppError::UnsupportedImage("Clipboard image has an unexpected buffer size".into()))?;
+    Ok(image::DynamicImage::ImageRgba8(buffer))
+}
 
-        model_manager::check_and_download_models(app, &model_path, &tags_path)
+/// Runs the tagger on `img`, loading (and if needed downloading) the configured model
+/// first, and returns the tags that pass the threshold and exclusion list, formatted
+/// according to `config`, together with their scores.
+pub async fn tag_image(
+    app: &AppHandle,
+    img: image::DynamicImage,
+    config: &AppConfig,
+) -> Result<Vec<(String, f32)>> {
+    let state = app.state::<AppState>();
+
+    // Read before inference so a missing exclusion file fails the request up front
+    let exclusions = config::exclusions(app, config)?;
+
+    let key = TaggerKey {
+        model_path: resolve_model_path(app, &config.model_path),
+        tags_path: resolve_model_path(app, &config.tags_path),
+        preprocessing: config.preprocessing.clone(),
+    };
+    // The shared tagger holds the configured model; runs using another one (profiles,
+    // overrides) go through `other_tagger`, which keeps the model they used last
+    let current = get_config(state.clone())?;
+    let shared = config.model_path == current.model_path
+        && config.tags_path == current.tags_path
+        && config.preprocessing == current.preprocessing;
+
+    let loaded = if shared {
+        lock(&state.tagger)?.is_some()
+    } else {
+        is_cached(&state.other_tagger, &key)?
+    };
+    if !loaded {
+        model_manager::check_and_download_models(app, &key.model_path, &key.tags_path)
             .await
             .context("Failed to check/download models")?;
+    }
 
-        let tagger = Tagger::new(
-            model_path.to_str().unwrap_or(&config.model_path),
-            tags_path.to_str().unwrap_or(&config.tags_path),
-            config.preprocessing.clone(),
-        )?;
+    // Loading a model and inference block for seconds; keep them off the async runtime
+    // so the HTTP API and the app stay responsive. The tagger lock is only held
+    // while the session runs, not while a model loads.
+    let threshold = config.threshold;
+    let handle = app.clone();
+    let results = tauri::async_runtime::spawn_blocking(move || -> Result<Vec<(String, f32)>> {
+        let state = handle.state::<AppState>();
+        if shared {
+            if lock(&state.tagger)?.is_none() {
+                let tagger = load_tagger(&key)?;
+                lock(&state.tagger)?.get_or_insert(tagger);
+            }
+            let mut guard = lock(&state.tagger)?;
+            let tagger = guard
+                .as_mut()
+                .ok_or_else(|| anyhow::anyhow!("Tagger not available"))?;
+            Ok(tagger.infer(&img, threshold)?)
+        } else {
+            if !is_cached(&state.other_tagger, &key)? {
+                // Free the previous model before loading the next one
+                lock(&state.other_tagger)?.take();
+                let tagger = load_tagger(&key)?;
+                *lock(&state.other_tagger)? = Some((key.clone(), tagger));
+            }
+            let mut guard = lock(&state.other_tagger)?;
+            match guard.as_mut() {
+                Some((k, tagger)) if *k == key => Ok(tagger.infer(&img, threshold)?),
+                _ => Err(anyhow::anyhow!("Tagger not available")),
+            }
+        }
+    })
+    .await
+    .context("Tagging task failed")??;
+    record_model_use(app, config);
 
-        *state
-            .tagger
-            .lock()
-            .map_err(|_| anyhow::anyhow!("Failed to lock tagger"))? = Some(tagger);
-    }
+    Ok(postprocess_tags(results, &exclusions, config.use_underscore))
+}
 
-    let mut tagger_guard = state
-        .tagger
+fn lock<T>(mutex: &Mutex<T>) -> Result<MutexGuard<'_, T>> {
+    mutex
         .lock()
-        .map_err(|_| anyhow::anyhow!("Failed to lock tagger"))?;
-    let tagger = tagger_guard
-        .as_mut()
-        .ok_or_else(|| anyhow::anyhow!("Tagger not available"))?;
+        .map_err(|_| anyhow::anyhow!("Failed to lock tagger"))
+}
 
-    let results = tagger.infer(&img, config.threshold)?;
+/// Whether `slot` holds the tagger described by `key`.
+fn is_cached(slot: &Mutex<Option<(TaggerKey, Tagger)>>, key: &TaggerKey) -> Result<bool> {
+    Ok(lock(slot)?.as_ref().is_some_and(|(k, _)| k == key))
+}
 
-    let mut filtered: Vec<String> = results
+fn load_tagger(key: &TaggerKey) -> Result<Tagger> {
+    Ok(Tagger::new(
+        &key.model_path.to_string_lossy(),
+        &key.tags_path.to_string_lossy(),
+        key.preprocessing.clone(),
+    )?)
+}
+
+fn record_model_use(app: &AppHandle, config: &AppConfig) {
+    if let Ok(models_dir) = model_manager::models_dir(app) {
+        let model_path = resolve_model_path(app, &config.model_path);
+        if let Err(e) = inventory::record_use(&models_dir, &model_path) {
+            eprintln!("Failed to record model usage: {}", e);
+        }
+    }
+}
+
+async fn run_inference_and_notify(
+    app: &AppHandle,
+    img: image::DynamicImage,
+    config: &AppConfig,
+    output: OutputTarget,
+    origin: &str,
+) -> Result<()> {
+    let filtered: Vec<String> = tag_image(app, img, config)
+        .await?
         .into_iter()
         .map(|(t, _)| t)
-        .filter(|t| !config.exclusion_list.contains(t))
         .collect();
+    let tags_str = filtered.join(", ");
 
-    if config.use_underscore {
-        filtered = filtered.iter().map(|t| t.replace(" ", "_")).collect();
-    } else {
-        filtered = filtered.iter().map(|t| t.replace("_", " ")).collect();
+    match output {
+        OutputTarget::Clipboard => {}
+        OutputTarget::Stdout => {
+            println!("{}", tags_str);
+            return Ok(());
+        }
+        OutputTarget::ResultsFile(path) => return append_result(&path, origin, &filtered),
     }
-    let tags_str = filtered.join(", ");
 
     let mut clipboard = arboard::Clipboard::new().context("Failed to access clipboard")?;
     clipboard
@@ -231,11 +492,31 @@ async fn run_inference_and_notify(app: &AppHandle, img: image::DynamicImage) ->
     Ok(())
 }
 
+/// Appends a single JSON line describing the tags generated for `origin`.
+fn append_result(path: &Path, origin: &str, tags: &[String]) -> Result<()> {
+    let record = TagRecord {
+        file: origin,
+        tags,
+        tagged_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs(),
+    };
+    let line = serde_json::to_string(&record)?;
+
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .context(format!("Failed to open results file {:?}", path))?;
+    writeln!(file, "{}", line).context("Failed to write results file")?;
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::fs;
-    use std::io::Write;
 
     #[tokio::test]
     async fn test_process_inputs_with_actions_delete_after() -> Result<(), Box<dyn std::error::Error>> {
@@ -256,8 +537,13 @@ mod tests {
             file_path.to_string_lossy().to_string(),
         ];
 
-        let result =
-            process_inputs_with_actions(args, |_| async { Ok(()) }, |_| async { Ok(()) }).await;
+        let result = process_inputs_with_actions(
+            args.into(),
+            |_, _| async { Ok(()) },
+            |_, _| async { Ok(()) },
+            |_, _| async { Ok(()) },
+        )
+        .await;
 
         assert!(result.is_ok());
         assert!(
@@ -285,8 +571,13 @@ mod tests {
             file_path.to_string_lossy().to_string(),
         ];
 
-        let result =
-            process_inputs_with_actions(args, |_| async { Ok(()) }, |_| async { Ok(()) }).await;
+        let result = process_inputs_with_actions(
+            args.into(),
+            |_, _| async { Ok(()) },
+            |_, _| async { Ok(()) },
+            |_, _| async { Ok(()) },
+        )
+        .await;
 
         assert!(result.is_ok());
         assert!(file_path.exists(), "File should NOT be deleted");
@@ -295,4 +586,245 @@ mod tests {
         fs::remove_file(file_path)?;
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_process_inputs_with_actions_stdin() {
+        for flag in ["-", "--stdin"] {
+            let args = vec!["app_name".to_string(), flag.to_string(), "--stdout".to_string()];
+            let mut seen = None;
+
+            let result = process_inputs_with_actions(
+                args.into(),
+                |_, _| async { Err(anyhow::anyhow!("URL processor should not run")) },
+                |_, _| async { Err(anyhow::anyhow!("File processor should not run")) },
+                |source, options: RunOptions| {
+                    seen = Some((source, options.output));
+                    async { Ok(()) }
+                },
+            )
+            .await;
+
+            assert!(result.is_ok());
+            assert_eq!(seen, Some((ImageSource::Stdin, OutputTarget::Stdout)));
+        }
+    }
+
+    #[test]
+    fn test_watch_job_args_accept_dashed_file_names() {
+        let args = crate::watcher::job_args(Path::new("-v.png"), Path::new("results.jsonl"));
+        let parsed = InvocationArgs::parse(&args, false).unwrap();
+        assert_eq!(parsed.inputs, vec!["-v.png".to_string()]);
+        assert_eq!(parsed.results_file, Some(PathBuf::from("results.jsonl")));
+    }
+
+    #[test]
+    fn test_wants_stdin_and_own_process() {
+        let args = |list: &[&str]| -> Vec<String> {
+            std::iter::once("omni-tagger").chain(list.iter().copied()).map(String::from).collect()
+        };
+        assert!(wants_stdin(&args(&["-"])));
+        assert!(wants_stdin(&args(&["--stdin", "--stdout"])));
+        assert!(!wants_stdin(&args(&["image.png"])));
+        // A "-" that is an option value, or an input overridden by --process-url
+        assert!(!wants_stdin(&args(&["--profile", "-", "image.png"])));
+        assert!(!wants_stdin(&args(&["--process-url", "https://example.com/a.png", "-"])));
+
+        let no_env = |_: &OsStr| None;
+        assert!(needs_own_process_with_env(&args(&["-"]), no_env));
+        assert!(needs_own_process_with_env(&args(&["image.png", "--stdout"]), no_env));
+        assert!(!needs_own_process_with_env(&args(&["image.png"]), no_env));
+        assert!(!needs_own_process_with_env(
+            &args(&["--results-file", "-", "image.png"]),
+            no_env
+        ));
+
+        // Overrides from the environment are only read for this process's own command
+        // line, so such runs are not forwarded either, unless the flag replaces them
+        let threshold = |name: &OsStr| (name == "OMNI_TAGGER_THRESHOLD").then(|| "0.9".into());
+        assert!(needs_own_process_with_env(&args(&["image.png"]), threshold));
+        assert!(!needs_own_process_with_env(
+            &args(&["image.png", "--threshold", "0.5"]),
+            threshold
+        ));
+        let empty = |_: &OsStr| Some(OsString::new());
+        assert!(!needs_own_process_with_env(&args(&["image.png"]), empty));
+    }
+
+    #[tokio::test]
+    async fn test_process_inputs_with_actions_from_clipboard() {
+        let args = vec!["app_name".to_string(), "--from-clipboard".to_string()];
+        let mut seen = None;
+
+        let result = process_inputs_with_actions(
+            args.into(),
+            |_, _| async { Ok(()) },
+            |_, _| async { Ok(()) },
+            |source, options: RunOptions| {
+                seen = Some((source, options.output));
+                async { Ok(()) }
+            },
+        )
+        .await;
+
+        assert!(result.is_ok());
+        assert_eq!(seen, Some((ImageSource::Clipboard, OutputTarget::Clipboard)));
+    }
+
+    #[test]
+    fn test_image_from_rgba() {
+        let img = image_from_rgba(2, 3, vec![255u8; 2 * 3 * 4]).unwrap();
+        assert_eq!((img.width(), img.height()), (2, 3));
+
+        assert!(image_from_rgba(2, 3, vec![0u8; 5]).is_err());
+        // Oversized bitmaps are refused before the buffer is wrapped
+        assert!(image_from_rgba(50_000, 50_000, Vec::new()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_process_inputs_with_actions_results_file() {
+        let args = vec![
+            "app_name".to_string(),
+            "--results-file".to_string(),
+            "/tmp/results.jsonl".to_string(),
+            "image.png".to_string(),
+            "--profile".to_string(),
+            "prompt copy".to_string(),
+        ];
+        let mut seen = None;
+
+        let result = process_inputs_with_actions(
+            args.into(),
+            |_, _| async { Ok(()) },
+            |path, options| {
+                seen = Some((path, options));
+                async { Ok(()) }
+            },
+            |_, _| async { Ok(()) },
+        )
+        .await;
+
+        assert!(result.is_ok());
+        assert_eq!(
+            seen,
+            Some((
+                PathBuf::from("image.png"),
+                RunOptions {
+                    output: OutputTarget::ResultsFile(PathBuf::from("/tmp/results.jsonl")),
+                    profile: Some("prompt copy".to_string()),
+                    ..Default::default()
+                }
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_process_inputs_with_actions_overrides() {
+        let args = vec![
+            "app_name".to_string(),
+            "--threshold".to_string(),
+            "0.5".to_string(),
+            "--no-underscore".to_string(),
+            "--exclude".to_string(),
+            "lowres,text".to_string(),
+            "--config".to_string(),
+            "/tmp/team/config.json".to_string(),
+            "--process-url".to_string(),
+            "https://example.com/a.png".to_string(),
+        ];
+        let mut seen = None;
+
+        let result = process_inputs_with_actions(
+            args.into(),
+            |url, options| {
+                seen = Some((url, options));
+                async { Ok(()) }
+            },
+            |_, _| async { Err(anyhow::anyhow!("File processor should not run")) },
+            |_, _| async { Err(anyhow::anyhow!("Source processor should not run")) },
+        )
+        .await;
+
+        assert!(result.is_ok());
+        let (url, options) = seen.unwrap();
+        assert_eq!(url, "https://example.com/a.png");
+        assert_eq!(options.config_path, Some(PathBuf::from("/tmp/team/config.json")));
+        assert_eq!(options.overrides.threshold, Some(0.5));
+        assert!(options.overrides.no_underscore);
+        assert_eq!(options.overrides.exclude, vec!["lowres", "text"]);
+    }
+
+    #[tokio::test]
+    async fn test_process_inputs_with_actions_resolves_forwarded_paths() {
+        let args = [
+            "app_name",
+            "--config",
+            "team/config.json",
+            "--results-file",
+            "out.jsonl",
+            "a.png",
+        ];
+        let job = Job {
+            cwd: Some(PathBuf::from("/home/user/project")),
+            ..Job::from(args.into_iter().map(String::from).collect::<Vec<_>>())
+        };
+        let mut seen = None;
+
+        let result = process_inputs_with_actions(
+            job,
+            |_, _| async { Err(anyhow::anyhow!("URL processor should not run")) },
+            |path, options| {
+                seen = Some((path, options));
+                async { Ok(()) }
+            },
+            |_, _| async { Err(anyhow::anyhow!("Source processor should not run")) },
+        )
+        .await;
+
+        assert!(result.is_ok());
+        let (path, options) = seen.unwrap();
+        let cwd = Path::new("/home/user/project");
+        assert_eq!(path, cwd.join("a.png"));
+        assert_eq!(options.config_path, Some(cwd.join("team/config.json")));
+        assert_eq!(options.output, OutputTarget::ResultsFile(cwd.join("out.jsonl")));
+    }
+
+    #[tokio::test]
+    async fn test_process_inputs_with_actions_rejects_bad_arguments() {
+        for args in [
+            vec!["app_name", "--threshold", "high", "a.png"],
+            vec!["app_name", "--unknown-flag", "a.png"],
+        ] {
+            let result = process_inputs_with_actions(
+                Job::from(args.into_iter().map(String::from).collect::<Vec<_>>()),
+                |_, _| async { Ok(()) },
+                |_, _| async { Err(anyhow::anyhow!("File processor should not run")) },
+                |_, _| async { Ok(()) },
+            )
+            .await;
+            assert_eq!(AppError::from(result.unwrap_err()).code(), "InvalidRequest");
+        }
+    }
+
+    #[test]
+    fn test_append_result() -> Result<(), Box<dyn std::error::Error>> {
+        let path = std::env::temp_dir().join("omni_tagger_test_results.jsonl");
+        let _ = fs::remove_file(&path);
+
+        append_result(&path, "a.png", &["1girl".to_string(), "solo".to_string()])?;
+        append_result(&path, "b.png", &[])?;
+
+        let content = fs::read_to_string(&path)?;
+        let lines: Vec<serde_json::Value> = content
+            .lines()
+            .map(serde_json::from_str)
+            .collect::<Result<_, _>>()?;
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["file"], "a.png");
+        assert_eq!(lines[0]["tags"][1], "solo");
+        assert_eq!(lines[1]["file"], "b.png");
+
+        fs::remove_file(path)?;
+        Ok(())
+    }
 }
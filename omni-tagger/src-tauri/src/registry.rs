@@ -2,11 +2,16 @@
 use std::fs;
 #[cfg(target_os = "windows")]
 use std::process::Command;
+use crate::error::AppError;
 use tauri::AppHandle;
 use tauri::{path::BaseDirectory, Manager};
 
 #[tauri::command]
-pub async fn register_context_menu(app: AppHandle, enable: bool) -> Result<(), String> {
+pub async fn register_context_menu(app: AppHandle, enable: bool) -> Result<(), AppError> {
+    set_context_menu(app, enable).await.map_err(AppError::Registration)
+}
+
+async fn set_context_menu(app: AppHandle, enable: bool) -> Result<(), String> {
     #[cfg(target_os = "windows")]
     {
         let _ = app; // unused on Windows for now, as we use current_exe directly
@@ -379,6 +384,16 @@ pub async fn register_native_host(
     app: AppHandle,
     extension_id: String,
     browser: Option<String>,
+) -> Result<(), AppError> {
+    install_native_host(app, extension_id, browser)
+        .await
+        .map_err(AppError::Registration)
+}
+
+async fn install_native_host(
+    app: AppHandle,
+    extension_id: String,
+    browser: Option<String>,
 ) -> Result<(), String> {
     let browser_type = browser.unwrap_or_else(|| "chromium".to_string());
 
@@ -728,7 +743,13 @@ pub async fn register_native_host(
 }
 
 #[tauri::command]
-pub async fn unregister_native_host(app: AppHandle, browser: Option<String>) -> Result<(), String> {
+pub async fn unregister_native_host(app: AppHandle, browser: Option<String>) -> Result<(), AppError> {
+    uninstall_native_host(app, browser)
+        .await
+        .map_err(AppError::Registration)
+}
+
+async fn uninstall_native_host(app: AppHandle, browser: Option<String>) -> Result<(), String> {
     let browser_type = browser.unwrap_or_else(|| "chromium".to_string());
 
     #[cfg(target_os = "windows")]
@@ -0,0 +1,397 @@
+use crate::config::{get_config, ApiServerConfig};
+use crate::error::AppError;
+use crate::model_manager;
+use crate::processor::tag_image;
+use crate::state::AppState;
+use axum::{
+    extract::{DefaultBodyLimit, FromRequest, Multipart, Request, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use omni_tagger_core::{decode, inventory};
+use serde::Serialize;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::TryLockError;
+use tauri::{AppHandle, Manager};
+
+#[derive(Clone)]
+struct ServerState {
+    app: AppHandle,
+    token: String,
+    port: u16,
+}
+
+struct ApiError {
+    status: StatusCode,
+    error: AppError,
+}
+
+impl From<AppError> for ApiError {
+    fn from(error: AppError) -> Self {
+        let status = match &error {
+            AppError::InvalidRequest(_) | AppError::InvalidUrl(_) => StatusCode::BAD_REQUEST,
+            AppError::Unauthorized => StatusCode::UNAUTHORIZED,
+            AppError::SsrfBlocked(_) | AppError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AppError::PayloadTooLarge { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+            AppError::UnsupportedImage(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            AppError::ModelMissing { .. } | AppError::DownloadFailed(_) => {
+                StatusCode::SERVICE_UNAVAILABLE
+            }
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        Self { status, error }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = serde_json::json!({
+            "status": "error",
+            "code": self.error.code(),
+            "message": self.error.to_string(),
+            "details": self.error.details(),
+        });
+        (self.status, Json(body)).into_response()
+    }
+}
+
+#[derive(Serialize)]
+struct TagEntry {
+    tag: String,
+    score: f32,
+}
+
+#[derive(Serialize)]
+struct TagResponse {
+    tags: Vec<TagEntry>,
+    text: String,
+}
+
+#[derive(Serialize)]
+struct ActiveModel {
+    model_path: String,
+    tags_path: String,
+    loaded: bool,
+}
+
+#[derive(Serialize)]
+struct ModelsResponse {
+    active: ActiveModel,
+    installed: Vec<String>,
+}
+
+/// Returns a random bearer token for the API server (256 bits, hex encoded).
+pub fn generate_token() -> Result<String, AppError> {
+    let mut bytes = [0u8; 32];
+    getrandom::fill(&mut bytes)
+        .map_err(|e| AppError::Internal(format!("Failed to generate API token: {}", e)))?;
+    Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Starts the HTTP API on `127.0.0.1:<port>`. The server only listens on the
+/// loopback interface and shares the `Tagger` loaded in `AppState`. It refuses to
+/// start without a token, so requests are never served unauthenticated.
+pub fn start(app: AppHandle, config: ApiServerConfig) {
+    if config.token.is_empty() {
+        eprintln!("API server not started: api_server.token is empty");
+        return;
+    }
+    tauri::async_runtime::spawn(async move {
+        let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, config.port));
+        let listener = match tokio::net::TcpListener::bind(addr).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                eprintln!("Failed to start API server on {}: {}", addr, e);
+                return;
+            }
+        };
+        println!("API server listening on http://{}", addr);
+
+        let router = router(ServerState {
+            app,
+            token: config.token,
+            port: config.port,
+        });
+        if let Err(e) = axum::serve(listener, router).await {
+            eprintln!("API server stopped: {}", e);
+        }
+    });
+}
+
+fn router(state: ServerState) -> Router {
+    Router::new()
+        .route("/health", get(health))
+        .route("/models", get(models))
+        .route("/tag", post(tag))
+        .layer(DefaultBodyLimit::max(decode::MAX_INPUT_SIZE as usize))
+        .with_state(state)
+}
+
+/// Checks that the request is addressed to this server and carries the
+/// `Authorization: Bearer <token>` header. An empty configured token matches nothing.
+fn authorize(headers: &HeaderMap, token: &str, port: u16) -> Result<(), ApiError> {
+    check_origin(headers, port)?;
+    let provided = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    match provided {
+        Some(provided) if !token.is_empty() && constant_time_eq(provided, token) => Ok(()),
+        _ => Err(AppError::Unauthorized.into()),
+    }
+}
+
+/// Rejects requests whose `Host` is not `127.0.0.1:<port>` or `localhost:<port>`,
+/// which is how DNS rebinding reaches a loopback server from a web page, and
+/// browser requests sent from another origin.
+fn check_origin(headers: &HeaderMap, port: u16) -> Result<(), ApiError> {
+    let local = |authority: &str| {
+        let authority = authority.to_ascii_lowercase();
+        authority == format!("127.0.0.1:{}", port) || authority == format!("localhost:{}", port)
+    };
+    let host = headers.get(header::HOST).and_then(|v| v.to_str().ok());
+    if !host.is_some_and(local) {
+        return Err(AppError::Forbidden(format!("unexpected Host {:?}", host.unwrap_or(""))).into());
+    }
+    if let Some(origin) = headers.get(header::ORIGIN) {
+        let origin = origin.to_str().unwrap_or("");
+        if !origin.strip_prefix("http://").is_some_and(local) {
+            return Err(AppError::Forbidden(format!("unexpected Origin {:?}", origin)).into());
+        }
+    }
+    Ok(())
+}
+
+/// Compares the tokens without returning early at the first differing byte, so
+/// response times do not reveal how much of a guess was right.
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a
+            .bytes()
+            .zip(b.bytes())
+            .fold(0u8, |diff, (x, y)| diff | (x ^ y))
+            == 0
+}
+
+fn is_multipart(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.starts_with("multipart/form-data"))
+        .unwrap_or(false)
+}
+
+/// Whether the configured model is loaded. The tagger is locked while an image is
+/// being tagged, which only happens once it is loaded; waiting for the lock would
+/// hold up this request until inference finishes.
+fn model_loaded(state: &AppState) -> bool {
+    match state.tagger.try_lock() {
+        Ok(tagger) => tagger.is_some(),
+        Err(TryLockError::WouldBlock) => true,
+        Err(TryLockError::Poisoned(_)) => false,
+    }
+}
+
+async fn health(
+    State(state): State<ServerState>,
+    headers: HeaderMap,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    authorize(&headers, &state.token, state.port)?;
+    let loaded = model_loaded(&state.app.state::<AppState>());
+    Ok(Json(serde_json::json!({ "status": "ok", "model_loaded": loaded })))
+}
+
+async fn models(
+    State(state): State<ServerState>,
+    headers: HeaderMap,
+) -> Result<Json<ModelsResponse>, ApiError> {
+    authorize(&headers, &state.token, state.port)?;
+    let app_state = state.app.state::<AppState>();
+    let config = app_state
+        .config
+        .lock()
+        .map_err(|e| ApiError::from(AppError::Internal(e.to_string())))?
+        .clone();
+    let loaded = model_loaded(&app_state);
+
+    let mut installed = Vec::new();
+    if let Ok(dir) = model_manager::models_dir(&state.app) {
+        for model in inventory::find_models(&dir) {
+            if let Ok(relative) = model.strip_prefix(&dir) {
+                let relative = relative.to_string_lossy().replace('\\', "/");
+                installed.push(format!("models/{}", relative));
+            }
+        }
+    }
+
+    Ok(Json(ModelsResponse {
+        active: ActiveModel {
+            model_path: config.model_path,
+            tags_path: config.tags_path,
+            loaded,
+        },
+        installed,
+    }))
+}
+
+async fn tag(
+    State(state): State<ServerState>,
+    request: Request,
+) -> Result<Json<TagResponse>, ApiError> {
+    let headers = request.headers().clone();
+    authorize(&headers, &state.token, state.port)?;
+
+    let bytes = if is_multipart(&headers) {
+        // Use the `image` field, or the first uploaded file if it is named differently
+        let mut multipart = Multipart::from_request(request, &state)
+            .await
+            .map_err(|e| ApiError::from(AppError::InvalidRequest(e.body_text())))?;
+        let mut data = None;
+        while let Some(field) = multipart
+            .next_field()
+            .await
+            .map_err(|e| ApiError::from(AppError::InvalidRequest(e.body_text())))?
+        {
+            if field.name() == Some("image") || field.file_name().is_some() {
+                data = Some(
+                    field
+                        .bytes()
+                        .await
+                        .map_err(|e| ApiError::from(AppError::InvalidRequest(e.body_text())))?,
+                );
+                break;
+            }
+        }
+        data.ok_or_else(|| {
+            ApiError::from(AppError::InvalidRequest(
+                "No image field in multipart body".into(),
+            ))
+        })?
+    } else {
+        axum::body::to_bytes(request.into_body(), decode::MAX_INPUT_SIZE as usize)
+            .await
+            .map_err(|_| {
+                ApiError::from(AppError::PayloadTooLarge {
+                    limit: decode::MAX_INPUT_SIZE,
+                })
+            })?
+    };
+
+    if bytes.is_empty() {
+        return Err(AppError::InvalidRequest("Request body is empty".into()).into());
+    }
+
+    let img = decode::decode_image(&bytes).map_err(|e| ApiError::from(AppError::from(e)))?;
+
+    let config = get_config(state.app.state::<AppState>()).map_err(ApiError::from)?;
+    let tags = tag_image(&state.app, img, &config)
+        .await
+        .map_err(|e| ApiError::from(AppError::from(e)))?;
+
+    let text = tags
+        .iter()
+        .map(|(t, _)| t.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+    Ok(Json(TagResponse {
+        tags: tags
+            .into_iter()
+            .map(|(tag, score)| TagEntry { tag, score })
+            .collect(),
+        text,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn test_authorize() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::HOST, HeaderValue::from_static("127.0.0.1:23813"));
+        assert!(authorize(&headers, "secret", 23813).is_err());
+
+        headers.insert(header::AUTHORIZATION, HeaderValue::from_static("Bearer wrong"));
+        assert!(authorize(&headers, "secret", 23813).is_err());
+
+        headers.insert(header::AUTHORIZATION, HeaderValue::from_static("Bearer secret"));
+        assert!(authorize(&headers, "secret", 23813).is_ok());
+
+        // An empty token never authorizes, not even an empty bearer
+        headers.insert(header::AUTHORIZATION, HeaderValue::from_static("Bearer "));
+        assert!(authorize(&headers, "", 23813).is_err());
+    }
+
+    #[test]
+    fn test_check_origin() {
+        let mut headers = HeaderMap::new();
+        assert!(check_origin(&headers, 23813).is_err());
+
+        for host in ["127.0.0.1:23813", "localhost:23813", "LOCALHOST:23813"] {
+            headers.insert(header::HOST, HeaderValue::from_static(host));
+            assert!(check_origin(&headers, 23813).is_ok(), "{}", host);
+        }
+        // DNS rebinding: the browser sends the attacker's host name
+        for host in ["evil.example:23813", "127.0.0.1:80", "127.0.0.1", "localhost"] {
+            headers.insert(header::HOST, HeaderValue::from_static(host));
+            let err = check_origin(&headers, 23813).unwrap_err();
+            assert_eq!(err.status, StatusCode::FORBIDDEN, "{}", host);
+        }
+
+        headers.insert(header::HOST, HeaderValue::from_static("127.0.0.1:23813"));
+        headers.insert(header::ORIGIN, HeaderValue::from_static("http://localhost:23813"));
+        assert!(check_origin(&headers, 23813).is_ok());
+        headers.insert(header::ORIGIN, HeaderValue::from_static("https://evil.example"));
+        assert!(check_origin(&headers, 23813).is_err());
+        headers.insert(header::ORIGIN, HeaderValue::from_static("null"));
+        assert!(check_origin(&headers, 23813).is_err());
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq("secret", "secret"));
+        assert!(!constant_time_eq("secret", "secreT"));
+        assert!(!constant_time_eq("secret", "secrets"));
+        assert!(constant_time_eq("", ""));
+    }
+
+    #[test]
+    fn test_generate_token() -> Result<(), Box<dyn std::error::Error>> {
+        let token = generate_token()?;
+        assert_eq!(token.len(), 64);
+        assert!(token.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(token, generate_token()?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_multipart() {
+        let mut headers = HeaderMap::new();
+        assert!(!is_multipart(&headers));
+
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("multipart/form-data; boundary=xyz"),
+        );
+        assert!(is_multipart(&headers));
+
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("image/png"));
+        assert!(!is_multipart(&headers));
+    }
+
+    #[test]
+    fn test_api_error_status() {
+        let err = ApiError::from(AppError::PayloadTooLarge { limit: 1 });
+        assert_eq!(err.status, StatusCode::PAYLOAD_TOO_LARGE);
+
+        let err = ApiError::from(AppError::model_missing(std::path::Path::new("model.onnx")));
+        assert_eq!(err.status, StatusCode::SERVICE_UNAVAILABLE);
+
+        let response = ApiError::from(AppError::Unauthorized).into_response();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+}
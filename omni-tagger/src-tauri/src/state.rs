@@ -1,11 +1,66 @@
-use crate::config::AppConfig;
-use crate::tagger::Tagger;
+use crate::config::{AppConfig, ConfigReset, PreprocessConfig};
+use omni_tagger_core::Tagger;
+use std::path::{Path, PathBuf};
 use std::sync::Mutex;
 
 pub struct AppState {
     pub tagger: Mutex<Option<Tagger>>,
+    /// Tagger for runs whose model differs from the configured one (profiles,
+    /// overrides), kept for the next run with the same model. Only the most
+    /// recently used one is kept, as each holds a whole model in memory.
+    pub other_tagger: Mutex<Option<(TaggerKey, Tagger)>>,
     pub config: Mutex<AppConfig>,
+    /// Set when `config.json` was unreadable at startup and the defaults were loaded.
+    pub config_reset: Mutex<Option<ConfigReset>>,
     pub download_lock: tokio::sync::Mutex<()>,
-    pub input_tx: tokio::sync::mpsc::UnboundedSender<Vec<String>>,
+    /// Cancelled by `cancel_download`; replaced when the next download starts.
+    pub download_cancel: Mutex<tokio_util::sync::CancellationToken>,
+    pub input_tx: tokio::sync::mpsc::UnboundedSender<Job>,
     pub active_tasks: std::sync::Arc<std::sync::atomic::AtomicUsize>,
+    pub stdin_image: Mutex<Option<Vec<u8>>>,
+}
+
+impl AppState {
+    /// Drops the cached [`AppState::other_tagger`] if it was loaded from `model_path`,
+    /// e.g. because the file was deleted or replaced.
+    pub fn forget_tagger(&self, model_path: &Path) {
+        if let Ok(mut slot) = self.other_tagger.lock() {
+            if slot.as_ref().is_some_and(|(key, _)| key.model_path == model_path) {
+                *slot = None;
+            }
+        }
+    }
+}
+
+/// The files and preprocessing a [`Tagger`] was loaded with.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaggerKey {
+    pub model_path: PathBuf,
+    pub tags_path: PathBuf,
+    pub preprocessing: PreprocessConfig,
+}
+
+/// A command line queued for the processing worker.
+pub struct Job {
+    pub args: Vec<String>,
+    /// Directory relative paths in `args` are resolved against, e.g. that of the
+    /// process that forwarded them; `None` uses this process's working directory.
+    pub cwd: Option<std::path::PathBuf>,
+    /// Whether `OMNI_TAGGER_*` environment variables apply. Only the command line
+    /// this process was started with reads them, never forwarded command lines or
+    /// jobs the app queues itself (tray, watch folders).
+    pub read_env: bool,
+    /// Receives whether processing succeeded, for senders that track their jobs.
+    pub done: Option<tokio::sync::oneshot::Sender<bool>>,
+}
+
+impl From<Vec<String>> for Job {
+    fn from(args: Vec<String>) -> Self {
+        Self {
+            args,
+            cwd: None,
+            read_env: false,
+            done: None,
+        }
+    }
 }
@@ -0,0 +1,389 @@
+use crate::state::{AppState, Job};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::time::{Duration, UNIX_EPOCH};
+use tauri::{path::BaseDirectory, AppHandle, Manager};
+use tokio::sync::oneshot;
+
+/// Name of the JSONL file written into each watched folder.
+pub const RESULTS_FILE_NAME: &str = "omni-tagger-results.jsonl";
+
+const STATE_FILE_NAME: &str = "watch_state.json";
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "webp", "gif", "bmp"];
+/// How often a file that fails to tag is tried before it is left alone until it
+/// changes again.
+const MAX_ATTEMPTS: u32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Snapshot {
+    len: u64,
+    modified: u64,
+}
+
+/// Files already tagged, keyed by path, with the modification time (milliseconds
+/// since the epoch) they had at that point.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct WatchState {
+    handled: HashMap<String, u64>,
+    /// Files whose tagging failed, so they are retried (up to [`MAX_ATTEMPTS`]
+    /// times per modification) instead of being skipped or retried forever.
+    #[serde(default)]
+    failed: HashMap<String, Failure>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct Failure {
+    modified: u64,
+    attempts: u32,
+}
+
+/// Tracks the contents of the watched folders between polls.
+///
+/// A file is only reported once its size and modification time are unchanged
+/// across two consecutive scans, so images that are still being written are
+/// skipped until the writer is done. Files are only marked as handled once
+/// [`FolderScanner::finish`] reports that their result was written.
+#[derive(Default)]
+struct FolderScanner {
+    observed: HashMap<PathBuf, Snapshot>,
+    /// Files queued for tagging whose outcome is not known yet.
+    queued: HashSet<PathBuf>,
+    state: WatchState,
+    /// Set when `state` changed since it was last saved.
+    dirty: bool,
+}
+
+impl FolderScanner {
+    fn new(state: WatchState) -> Self {
+        Self {
+            state,
+            ..Self::default()
+        }
+    }
+
+    /// Scans `folders` and returns the files that are ready to be tagged, with
+    /// their modification time. They are not scanned again until [`Self::finish`]
+    /// is called for them.
+    fn scan(&mut self, folders: &[PathBuf]) -> Vec<(PathBuf, u64)> {
+        let mut current = HashMap::new();
+        let mut scanned = HashSet::new();
+        for folder in folders {
+            let entries = match fs::read_dir(folder) {
+                Ok(entries) => entries,
+                Err(e) => {
+                    eprintln!("Failed to read watch folder {:?}: {}", folder, e);
+                    continue;
+                }
+            };
+            scanned.insert(folder.as_path());
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if !is_image_file(&path) {
+                    continue;
+                }
+                if let Some(snapshot) = snapshot(&path) {
+                    current.insert(path, snapshot);
+                }
+            }
+        }
+
+        // Forget files that were deleted from a folder that could be read; entries
+        // of unreadable folders (e.g. an unmounted drive) are kept
+        let before = self.state.handled.len() + self.state.failed.len();
+        let exists = |key: &String| {
+            let path = Path::new(key);
+            current.contains_key(path)
+                || !path.parent().is_some_and(|parent| scanned.contains(parent))
+        };
+        self.state.handled.retain(|key, _| exists(key));
+        self.state.failed.retain(|key, _| exists(key));
+        if self.state.handled.len() + self.state.failed.len() != before {
+            self.dirty = true;
+        }
+
+        let mut ready = Vec::new();
+        for (path, snapshot) in &current {
+            if self.observed.get(path) != Some(snapshot) || self.queued.contains(path) {
+                continue; // New or still changing, wait for the next poll
+            }
+            let key = path.to_string_lossy().into_owned();
+            if self.state.handled.get(&key) == Some(&snapshot.modified) {
+                continue;
+            }
+            if let Some(failure) = self.state.failed.get(&key) {
+                if failure.modified == snapshot.modified && failure.attempts >= MAX_ATTEMPTS {
+                    continue;
+                }
+            }
+            self.queued.insert(path.clone());
+            ready.push((path.clone(), snapshot.modified));
+        }
+
+        self.observed = current;
+        ready.sort();
+        ready
+    }
+
+    /// Records the outcome of tagging `path` as it was at `modified`. Successful
+    /// files are not queued again until they change; failed ones are retried.
+    fn finish(&mut self, path: &Path, modified: u64, success: bool) {
+        self.queued.remove(path);
+        let key = path.to_string_lossy().into_owned();
+        if success {
+            self.state.failed.remove(&key);
+            self.state.handled.insert(key, modified);
+        } else {
+            let attempts = match self.state.failed.get(&key) {
+                Some(failure) if failure.modified == modified => failure.attempts + 1,
+                _ => 1,
+            };
+            self.state.failed.insert(key, Failure { modified, attempts });
+        }
+        self.dirty = true;
+    }
+}
+
+fn is_image_file(path: &Path) -> bool {
+    path.is_file()
+        && path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| IMAGE_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+            .unwrap_or(false)
+}
+
+fn snapshot(path: &Path) -> Option<Snapshot> {
+    let metadata = fs::metadata(path).ok()?;
+    let modified = metadata
+        .modified()
+        .ok()?
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64;
+    Some(Snapshot {
+        len: metadata.len(),
+        modified,
+    })
+}
+
+fn state_path(app: &AppHandle) -> Option<PathBuf> {
+    app.path()
+        .resolve(STATE_FILE_NAME, BaseDirectory::AppLocalData)
+        .ok()
+}
+
+fn load_state(path: &Path) -> WatchState {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn save_state(path: &Path, state: &WatchState) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let content = serde_json::to_string(state).map_err(|e| e.to_string())?;
+    fs::write(path, content).map_err(|e| e.to_string())
+}
+
+/// Command line that tags `path` into `results_path`. The file name comes after
+/// `--`, so names starting with `-` are not taken for options.
+pub(crate) fn job_args(path: &Path, results_path: &Path) -> Vec<String> {
+    vec![
+        "omni-tagger".to_string(),
+        "--results-file".to_string(),
+        results_path.to_string_lossy().into_owned(),
+        "--".to_string(),
+        path.to_string_lossy().into_owned(),
+    ]
+}
+
+/// Starts the background poller that queues new or modified images found in the
+/// configured watch folders. Results are appended to `RESULTS_FILE_NAME` inside
+/// each folder instead of being copied to the clipboard.
+pub fn start(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let state_path = state_path(&app);
+        let mut scanner =
+            FolderScanner::new(state_path.as_deref().map(load_state).unwrap_or_default());
+        let mut pending: Vec<(PathBuf, u64, oneshot::Receiver<bool>)> = Vec::new();
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            // Collect the outcome of jobs the worker has finished since the last poll
+            pending.retain_mut(|(path, modified, done)| match done.try_recv() {
+                Err(oneshot::error::TryRecvError::Empty) => true,
+                // A dropped sender means the job was lost; treat it as failed
+                result => {
+                    scanner.finish(path, *modified, result.unwrap_or(false));
+                    false
+                }
+            });
+            if scanner.dirty {
+                if let Some(path) = &state_path {
+                    match save_state(path, &scanner.state) {
+                        Ok(()) => scanner.dirty = false,
+                        Err(e) => eprintln!("Failed to save watch state: {}", e),
+                    }
+                }
+            }
+
+            let state = app.state::<AppState>();
+            let (enabled, folders) = match state.config.lock() {
+                Ok(config) => (
+                    config.watch_enabled,
+                    config
+                        .watch_folders
+                        .iter()
+                        .map(PathBuf::from)
+                        .collect::<Vec<_>>(),
+                ),
+                Err(_) => continue,
+            };
+
+            if !enabled || folders.is_empty() {
+                scanner.observed.clear();
+                continue;
+            }
+
+            for (path, modified) in scanner.scan(&folders) {
+                let results_path = path
+                    .parent()
+                    .unwrap_or_else(|| Path::new("."))
+                    .join(RESULTS_FILE_NAME);
+                let (done, outcome) = oneshot::channel();
+                state.active_tasks.fetch_add(1, Ordering::SeqCst);
+                let _ = state.input_tx.send(Job {
+                    args: job_args(&path, &results_path),
+                    done: Some(done),
+                    ..Job::from(Vec::new())
+                });
+                pending.push((path, modified, outcome));
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scan_waits_for_stable_files() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = std::env::temp_dir().join("omni_tagger_test_watch");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir)?;
+        let image = dir.join("new.png");
+        fs::write(&image, "partial")?;
+        fs::write(dir.join("notes.txt"), "not an image")?;
+
+        let folders = vec![dir.clone()];
+        let mut scanner = FolderScanner::default();
+
+        // First sighting only records the snapshot
+        assert!(scanner.scan(&folders).is_empty());
+
+        // Still being written: size changed since the last poll
+        fs::write(&image, "partial, now complete")?;
+        assert!(scanner.scan(&folders).is_empty());
+
+        // Unchanged across two polls: ready exactly once
+        let ready = scanner.scan(&folders);
+        assert_eq!(ready.len(), 1);
+        let (path, modified) = ready[0].clone();
+        assert_eq!(path, image);
+        assert!(scanner.scan(&folders).is_empty());
+
+        // Not handled until the result is written
+        assert!(scanner.state.handled.is_empty());
+        scanner.finish(&path, modified, true);
+        assert!(scanner.dirty);
+        assert!(scanner.scan(&folders).is_empty());
+
+        // Handled files survive a restart
+        let mut restarted = FolderScanner::new(WatchState {
+            handled: scanner.state.handled.clone(),
+            failed: HashMap::new(),
+        });
+        assert!(restarted.scan(&folders).is_empty());
+        assert!(restarted.scan(&folders).is_empty());
+
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_failed_files_are_retried() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = std::env::temp_dir().join("omni_tagger_test_watch_retry");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir)?;
+        let image = dir.join("broken.png");
+        fs::write(&image, "not really a png")?;
+
+        let folders = vec![dir.clone()];
+        let mut scanner = FolderScanner::default();
+        assert!(scanner.scan(&folders).is_empty());
+
+        for _ in 0..MAX_ATTEMPTS {
+            let ready = scanner.scan(&folders);
+            assert_eq!(ready.len(), 1);
+            scanner.finish(&ready[0].0, ready[0].1, false);
+        }
+        // Given up until the file changes
+        assert!(scanner.scan(&folders).is_empty());
+        assert!(scanner.state.handled.is_empty());
+
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_deleted_files_are_pruned() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = std::env::temp_dir().join("omni_tagger_test_watch_prune");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir)?;
+        let image = dir.join("gone.png");
+        fs::write(&image, "image")?;
+
+        let mut scanner = FolderScanner::new(WatchState {
+            handled: HashMap::from([
+                (image.to_string_lossy().into_owned(), 1),
+                (dir.join("deleted.png").to_string_lossy().into_owned(), 1),
+                ("/unreadable/folder/kept.png".to_string(), 1),
+            ]),
+            failed: HashMap::new(),
+        });
+        scanner.scan(&[dir.clone(), PathBuf::from("/unreadable/folder")]);
+        assert_eq!(scanner.state.handled.len(), 2);
+        assert!(scanner.state.handled.contains_key(image.to_string_lossy().as_ref()));
+        assert!(scanner.state.handled.contains_key("/unreadable/folder/kept.png"));
+        assert!(scanner.dirty);
+
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_image_file() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = std::env::temp_dir().join("omni_tagger_test_watch_ext");
+        fs::create_dir_all(&dir)?;
+        let upper = dir.join("photo.JPG");
+        let results = dir.join(RESULTS_FILE_NAME);
+        fs::write(&upper, "")?;
+        fs::write(&results, "")?;
+
+        assert!(is_image_file(&upper));
+        assert!(!is_image_file(&results));
+        assert!(!is_image_file(&dir.join("missing.png")));
+
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+}
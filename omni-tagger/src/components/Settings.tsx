@@ -1,61 +1,175 @@
 import { useState, useEffect, useRef, useCallback } from 'react';
 import { invoke } from "@tauri-apps/api/core";
 import { listen } from "@tauri-apps/api/event";
-import { open } from '@tauri-apps/plugin-dialog';
+import { open, save } from '@tauri-apps/plugin-dialog';
 
 interface DownloadProgress {
     file: string;
     total: number;
     downloaded: number;
     percent: number;
+    bytes_per_sec: number;
+    eta_secs: number | null;
+    file_index: number;
+    file_count: number;
+    overall_percent: number;
+    // Set for `model-download-indeterminate` events, where `total` is unknown
+    indeterminate?: boolean;
 }
 
+const formatMiB = (bytes: number) => (bytes / 1048576).toFixed(1) + " MiB";
+
+const describeProgress = (p: DownloadProgress) => {
+    const rate = formatMiB(p.bytes_per_sec) + "/s";
+    if (p.indeterminate) return `${p.file}: ${formatMiB(p.downloaded)} (${rate})`;
+    const eta = p.eta_secs != null ? `, ${Math.ceil(p.eta_secs)}s left` : "";
+    return `${p.file}: ${p.percent.toFixed(1)}% (${rate}${eta})`;
+};
+
 interface PreprocessConfig {
     input_size: number;
-    format: string;
+    format: 'bgr' | 'rgb';
     normalize: boolean;
 }
 
+// Entry of `details.errors` of an `InvalidConfig` error
+interface FieldError {
+    field: string;
+    message: string;
+}
+
+interface ApiServerConfig {
+    enabled: boolean;
+    port: number;
+    token: string;
+}
+
+interface UrlPolicy {
+    allow_domains: string[];
+    deny_domains: string[];
+    allow_private_networks: boolean;
+    max_size: number;
+    timeout_secs: number;
+    connect_timeout_secs: number;
+    retries: number;
+}
+
+interface NetworkConfig {
+    proxy: string;
+    no_proxy: string[];
+    ca_bundle: string;
+}
+
+// Serialized form of the backend `AppError`
+interface AppError {
+    code: string;
+    message: string;
+    details: { path?: string; limit?: number; errors?: FieldError[] } | null;
+}
+
+const isAppError = (e: unknown): e is AppError =>
+    typeof e === 'object' && e !== null && 'code' in e && 'message' in e;
+
+const errorMessage = (e: unknown) => isAppError(e) ? e.message : String(e);
+
+/** Random 256-bit bearer token for the local HTTP API, hex encoded. */
+const generateToken = () =>
+    Array.from(crypto.getRandomValues(new Uint8Array(32)), (b) => b.toString(16).padStart(2, '0')).join('');
+
+interface ModelCheck {
+    path: string;
+    status: 'ok' | 'mismatch' | 'unverified' | 'missing';
+    sha256?: string;
+    expected?: string;
+    actual?: string;
+}
+
+// Sent when config.json could not be loaded and the defaults were used
+interface ConfigReset {
+    reason: string;
+    backup_path: string | null;
+}
+
 interface AppConfig {
+  version: number;
   model_path: string;
   tags_path: string;
   threshold: number;
   use_underscore: boolean;
   exclusion_list: string[];
+  exclusion_file: string | null;
   preprocessing: PreprocessConfig;
+  watch_folders: string[];
+  watch_enabled: boolean;
+  api_server: ApiServerConfig;
+  model_hub: { base_url: string };
+  network: NetworkConfig;
+  url_policy: UrlPolicy;
+  active_profile: string | null;
 }
 
-const PRESETS = [
-    {
-        name: "WD14 SwinV2 (Default)",
-        path: "models/model.onnx",
-        url: "https://huggingface.co/SmilingWolf/wd-v1-4-swinv2-tagger-v2/resolve/main/model.onnx",
-        preprocessing: { input_size: 448, format: "bgr", normalize: false }
-    },
-    {
-        name: "WD14 ConvNext",
-        path: "models/convnext.onnx",
-        url: "https://huggingface.co/SmilingWolf/wd-v1-4-convnext-tagger-v2/resolve/main/model.onnx",
-        preprocessing: { input_size: 448, format: "bgr", normalize: false }
-    },
-    {
-        name: "WD14 ConvNextV2",
-        path: "models/convnextv2.onnx",
-        url: "https://huggingface.co/SmilingWolf/wd-v1-4-convnextv2-tagger-v2/resolve/main/model.onnx",
-        preprocessing: { input_size: 448, format: "bgr", normalize: false }
-    }
+// Settings groups accepted by `export_config` / `import_config`
+type ConfigSection = 'exclusions' | 'formatting' | 'model' | 'network';
+
+const CONFIG_SECTIONS: { id: ConfigSection; label: string }[] = [
+    { id: 'exclusions', label: 'Exclusion list' },
+    { id: 'formatting', label: 'Threshold & formatting' },
+    { id: 'model', label: 'Model' },
+    { id: 'network', label: 'Network & image URLs' },
 ];
-const TAGS_URL = "https://huggingface.co/SmilingWolf/wd-v1-4-swinv2-tagger-v2/resolve/main/selected_tags.csv";
-const TAGS_PATH = "models/tags.csv";
+
+interface ModelInfo {
+    path: string;
+    size: number;
+    checksum: { status: ModelCheck['status'] };
+    tags_path: string | null;
+    tag_count: number | null;
+    input_shape: number[] | null;
+    last_used: number | null;
+}
+
+interface InstalledModels {
+    models: ModelInfo[];
+    disk_usage: number;
+}
+
+// Entry of the model catalog (`get_model_catalog`)
+interface ModelEntry {
+    id: string;
+    name: string;
+    model_url: string;
+    tags_url: string;
+    model_path: string;
+    tags_path: string;
+    sha256?: string;
+    size?: number;
+    preprocessing: PreprocessConfig;
+}
 
 export default function Settings() {
   const [config, setConfig] = useState<AppConfig | null>(null);
   const [loading, setLoading] = useState(true);
   const [exclusionText, setExclusionText] = useState("");
+  const [noProxyText, setNoProxyText] = useState("");
+  const [allowDomainsText, setAllowDomainsText] = useState("");
+  const [denyDomainsText, setDenyDomainsText] = useState("");
   const [downloadProgress, setDownloadProgress] = useState<DownloadProgress | null>(null);
   const [modelStatus, setModelStatus] = useState<'checking' | 'present' | 'missing'>('checking');
   const [extensionId, setExtensionId] = useState("");
   const [browserType, setBrowserType] = useState("chromium");
+  const [lastError, setLastError] = useState<AppError | null>(null);
+  const [configReset, setConfigReset] = useState<ConfigReset | null>(null);
+  const [fieldErrors, setFieldErrors] = useState<FieldError[]>([]);
+  const [profiles, setProfiles] = useState<string[]>([]);
+  const [modelChecks, setModelChecks] = useState<ModelCheck[] | null>(null);
+  const [verifying, setVerifying] = useState(false);
+  const [catalog, setCatalog] = useState<ModelEntry[]>([]);
+  const [installed, setInstalled] = useState<InstalledModels | null>(null);
+  const [listingModels, setListingModels] = useState(false);
+  const [hubRepo, setHubRepo] = useState("");
+  const [hubRevision, setHubRevision] = useState("");
+  const [shareSections, setShareSections] = useState<ConfigSection[]>([]);
+  const [mergeImport, setMergeImport] = useState(true);
 
   const configRef = useRef(config);
   useEffect(() => {
@@ -74,11 +188,21 @@ export default function Settings() {
           });
   }, []);
 
+  const loadProfiles = useCallback(() => {
+      invoke<string[]>('list_profiles')
+          .then(setProfiles)
+          .catch(e => console.error("Failed to list profiles", e));
+  }, []);
+
   useEffect(() => {
+    loadProfiles();
     invoke<AppConfig>('get_config')
       .then(c => {
         setConfig(c);
         setExclusionText(c.exclusion_list.join(", "));
+        setNoProxyText(c.network.no_proxy.join(", "));
+        setAllowDomainsText(c.url_policy.allow_domains.join(", "));
+        setDenyDomainsText(c.url_policy.deny_domains.join(", "));
         setLoading(false);
       })
       .catch(e => {
@@ -86,20 +210,60 @@ export default function Settings() {
         setLoading(false);
       });
 
+    // The reset happens at startup, usually before this window is listening
+    invoke<ConfigReset | null>('get_config_reset')
+      .then(setConfigReset)
+      .catch(e => console.error("Failed to check config reset", e));
+
+    invoke<ModelEntry[]>('get_model_catalog')
+      .then(setCatalog)
+      .catch(e => console.error("Failed to load model catalog", e));
+
     const unlistenProgress = listen<DownloadProgress>('model-download-progress', (event) => {
         setDownloadProgress(event.payload);
     });
 
+    const unlistenIndeterminate = listen<DownloadProgress>('model-download-indeterminate', (event) => {
+        setDownloadProgress({ ...event.payload, indeterminate: true });
+    });
+
     const unlistenFinished = listen('model-download-finished', () => {
         setDownloadProgress(null);
         if (configRef.current) checkModel(configRef.current.model_path);
     });
 
+    const unlistenConfig = listen('config-changed', () => {
+        invoke<AppConfig>('get_config')
+            .then(c => {
+                setConfig(c);
+                setExclusionText(c.exclusion_list.join(", "));
+            })
+            .catch(e => console.error("Failed to reload config", e));
+        loadProfiles();
+    });
+
+    const unlistenReset = listen<ConfigReset>('config-reset', (event) => {
+        setConfigReset(event.payload);
+    });
+
+    const unlistenError = listen<AppError>('processing-error', (event) => {
+        if (event.payload.code === 'Cancelled') {
+            setDownloadProgress(null);
+            return;
+        }
+        setLastError(event.payload);
+        if (event.payload.code === 'ModelMissing') setModelStatus('missing');
+    });
+
     return () => {
         unlistenProgress.then(f => f());
+        unlistenIndeterminate.then(f => f());
         unlistenFinished.then(f => f());
+        unlistenConfig.then(f => f());
+        unlistenReset.then(f => f());
+        unlistenError.then(f => f());
     };
-  }, [checkModel]);
+  }, [checkModel, loadProfiles]);
 
   // Check model status when config.model_path changes
   const modelPath = config?.model_path;
@@ -129,18 +293,93 @@ export default function Settings() {
 
   const downloadCurrentModel = async () => {
       if (!config) return;
-      const preset = PRESETS.find(p => p.path === config.model_path);
-      if (!preset) return;
+      const entry = catalog.find(m => m.model_path === config.model_path);
+      if (!entry) return;
 
       try {
-          await invoke('download_new_model', { url: preset.url, pathStr: preset.path });
-          const tagsExists = await invoke<boolean>('check_model_exists', { pathStr: config.tags_path });
-          if (!tagsExists) {
-               await invoke('download_new_model', { url: TAGS_URL, pathStr: TAGS_PATH });
-          }
+          await invoke('download_catalog_model', { id: entry.id });
+          setLastError(null);
       } catch (e) {
+          setDownloadProgress(null);
+          if (isAppError(e) && e.code === 'Cancelled') return;
           console.error("Failed to download", e);
-          alert("Download failed: " + e);
+          alert("Download failed: " + errorMessage(e));
+      }
+  };
+
+  const downloadHubModel = async () => {
+      try {
+          await invoke<ModelEntry>('download_hub_model', {
+              repoId: hubRepo,
+              revision: hubRevision || null
+          });
+          setCatalog(await invoke<ModelEntry[]>('get_model_catalog'));
+          setHubRepo("");
+          setHubRevision("");
+      } catch (e) {
+          setDownloadProgress(null);
+          if (isAppError(e) && e.code === 'Cancelled') return;
+          console.error("Failed to download from Hugging Face", e);
+          alert("Download failed: " + errorMessage(e));
+      }
+  };
+
+  const importModel = async (bundle: boolean) => {
+      try {
+          let entry: ModelEntry;
+          if (bundle) {
+              const path = await open({
+                  filters: [{ name: 'Model Bundle', extensions: ['zip', 'tar', 'gz', 'tgz'] }]
+              });
+              if (!path || typeof path !== 'string') return;
+              entry = await invoke<ModelEntry>('import_model', { path });
+          } else {
+              const path = await open({ filters: [{ name: 'ONNX Model', extensions: ['onnx'] }] });
+              if (!path || typeof path !== 'string') return;
+              const tagsPath = await open({ filters: [{ name: 'CSV File', extensions: ['csv'] }] });
+              if (!tagsPath || typeof tagsPath !== 'string') return;
+              entry = await invoke<ModelEntry>('import_model', { path, tagsPath });
+          }
+          setCatalog(await invoke<ModelEntry[]>('get_model_catalog'));
+          alert(`Imported ${entry.name}. Select it under Model Preset to use it.`);
+      } catch (e) {
+          alert("Import failed: " + errorMessage(e));
+      }
+  };
+
+  const listInstalledModels = async () => {
+      setListingModels(true);
+      try {
+          setInstalled(await invoke<InstalledModels>('list_installed_models'));
+      } catch (e) {
+          alert("Failed to list models: " + errorMessage(e));
+      } finally {
+          setListingModels(false);
+      }
+  };
+
+  const deleteModel = async (path: string) => {
+      if (!confirm(`Delete ${path}?`)) return;
+      try {
+          await invoke<number>('delete_model', { pathStr: path });
+          await listInstalledModels();
+      } catch (e) {
+          alert("Failed to delete model: " + errorMessage(e));
+      }
+  };
+
+  const cancelDownload = () => {
+      invoke('cancel_download').catch(e => console.error("Failed to cancel download", e));
+  };
+
+  const verifyModels = async () => {
+      setVerifying(true);
+      try {
+          setModelChecks(await invoke<ModelCheck[]>('verify_models'));
+      } catch (e) {
+          alert("Verification failed: " + errorMessage(e));
+      } finally {
+          setVerifying(false);
       }
   };
 
@@ -148,9 +387,84 @@ export default function Settings() {
       setConfig(newConfig);
       try {
           await invoke('set_config', { config: newConfig });
+          setFieldErrors([]);
       } catch (e) {
           console.error("Failed to save config", e);
-          alert("Failed to save config: " + e);
+          if (isAppError(e) && e.code === 'InvalidConfig') {
+              // Nothing was saved; keep the edit visible so it can be corrected
+              setFieldErrors(e.details?.errors ?? []);
+              return;
+          }
+          if (isAppError(e) && e.code === 'ModelMissing') {
+              // The settings were saved; the model just needs to be downloaded
+              setModelStatus('missing');
+              setLastError(e);
+              return;
+          }
+          alert("Failed to save config: " + errorMessage(e));
+      }
+  };
+
+  const switchProfile = async (name: string) => {
+      try {
+          // The backend emits `config-changed`, which reloads the settings shown here
+          await invoke('switch_profile', { name });
+          setFieldErrors([]);
+      } catch (e) {
+          if (isAppError(e) && e.code === 'InvalidConfig') {
+              setFieldErrors(e.details?.errors ?? []);
+              return;
+          }
+          alert("Failed to switch profile: " + errorMessage(e));
+      }
+  };
+
+  const saveProfile = async () => {
+      const name = prompt("Save the current threshold, formatting and model settings as profile:", config?.active_profile ?? "");
+      if (!name) return;
+      try {
+          await invoke('save_profile', { name: name.trim() });
+      } catch (e) {
+          alert("Failed to save profile: " + errorMessage(e));
+      }
+  };
+
+  const deleteProfile = async (name: string) => {
+      if (!confirm(`Delete profile ${name}?`)) return;
+      try {
+          await invoke('delete_profile', { name });
+      } catch (e) {
+          alert("Failed to delete profile: " + errorMessage(e));
+      }
+  };
+
+  const exportSettings = async () => {
+      const path = await save({
+          defaultPath: 'omni-tagger-settings.json',
+          filters: [{ name: 'JSON', extensions: ['json'] }],
+      });
+      if (!path) return;
+      try {
+          await invoke('export_config', { path, sections: shareSections });
+      } catch (e) {
+          alert("Failed to export settings: " + errorMessage(e));
+      }
+  };
+
+  const importSettings = async () => {
+      const path = await open({ multiple: false, filters: [{ name: 'JSON', extensions: ['json'] }] });
+      if (typeof path !== 'string') return;
+      try {
+          const imported = await invoke<AppConfig>('import_config', { path, sections: shareSections, merge: mergeImport });
+          setConfig(imported);
+          setExclusionText(imported.exclusion_list.join(", "));
+          setFieldErrors([]);
+      } catch (e) {
+          if (isAppError(e) && e.code === 'InvalidConfig') {
+              setFieldErrors(e.details?.errors ?? []);
+              return;
+          }
+          alert("Failed to import settings: " + errorMessage(e));
       }
   };
 
@@ -167,12 +481,36 @@ export default function Settings() {
       saveConfig(newConfig);
   };
 
+  const updateApiServer = <K extends keyof ApiServerConfig>(key: K, value: ApiServerConfig[K]) => {
+      if (!config) return;
+      const apiServer = { ...config.api_server, [key]: value };
+      if (apiServer.enabled && !apiServer.token && key === 'enabled') {
+          // The server never runs without authentication
+          apiServer.token = generateToken();
+      }
+      saveConfig({ ...config, api_server: apiServer });
+  };
+
+  const updateNetwork = <K extends keyof NetworkConfig>(key: K, value: NetworkConfig[K]) => {
+      if (!config) return;
+      const newConfig = { ...config, network: { ...config.network, [key]: value } };
+      saveConfig(newConfig);
+  };
+
+  const updateUrlPolicy = <K extends keyof UrlPolicy>(key: K, value: UrlPolicy[K]) => {
+      if (!config) return;
+      const newConfig = { ...config, url_policy: { ...config.url_policy, [key]: value } };
+      saveConfig(newConfig);
+  };
+
+  const splitList = (text: string) => text.split(",").map(s => s.trim()).filter(s => s.length > 0);
+
   const registerContextMenu = async (enable: boolean) => {
       try {
           await invoke('register_context_menu', { enable });
           alert(`Successfully ${enable ? 'added to' : 'removed from'} Context Menu.`);
       } catch (e) {
-          alert("Failed: " + e);
+          alert("Failed: " + errorMessage(e));
       }
   };
 
@@ -185,7 +523,7 @@ export default function Settings() {
           await invoke('register_native_host', { extensionId, browser: browserType });
           alert("Native Host registered successfully!");
       } catch (e) {
-          alert("Failed: " + e);
+          alert("Failed: " + errorMessage(e));
       }
   };
 
@@ -194,7 +532,7 @@ export default function Settings() {
           await invoke('unregister_native_host', { browser: browserType });
           alert("Native Host unregistered successfully!");
       } catch (e) {
-          alert("Failed: " + e);
+          alert("Failed: " + errorMessage(e));
       }
   };
 
@@ -207,11 +545,55 @@ export default function Settings() {
 
       {downloadProgress && (
         <div className="bg-blue-100 border-l-4 border-blue-500 text-blue-700 p-4 mb-4" role="alert">
-            <p className="font-bold">Downloading Model...</p>
-            <p className="text-sm mb-2">{downloadProgress.file}: {downloadProgress.percent.toFixed(1)}%</p>
+            <div className="flex justify-between items-start">
+                <p className="font-bold">
+                    Downloading Model...
+                    {downloadProgress.file_count > 1 && ` (file ${downloadProgress.file_index + 1} of ${downloadProgress.file_count})`}
+                </p>
+                <button onClick={cancelDownload} className="text-sm underline">Cancel</button>
+            </div>
+            <p className="text-sm mb-2">{describeProgress(downloadProgress)}</p>
             <div className="w-full bg-blue-200 rounded-full h-2.5 dark:bg-blue-200">
-                <div className="bg-blue-600 h-2.5 rounded-full" style={{ width: `${downloadProgress.percent}%` }}></div>
+                {downloadProgress.indeterminate ? (
+                    <div className="bg-blue-600 h-2.5 rounded-full w-full animate-pulse"></div>
+                ) : (
+                    <div className="bg-blue-600 h-2.5 rounded-full" style={{ width: `${downloadProgress.overall_percent}%` }}></div>
+                )}
+            </div>
+        </div>
+      )}
+
+      {configReset && (
+        <div className="bg-yellow-100 border-l-4 border-yellow-500 text-yellow-800 p-4 mb-4 flex justify-between items-start" role="alert">
+            <div>
+                <p className="font-bold">Settings were reset to defaults</p>
+                <p className="text-sm">{configReset.reason}</p>
+                {configReset.backup_path && (
+                    <p className="text-sm">The previous file was kept at <code>{configReset.backup_path}</code>.</p>
+                )}
             </div>
+            <button onClick={() => setConfigReset(null)} className="text-sm underline">Dismiss</button>
+        </div>
+      )}
+
+      {fieldErrors.length > 0 && (
+        <div className="bg-red-100 border-l-4 border-red-500 text-red-700 p-4 mb-4" role="alert">
+            <p className="font-bold">Settings not saved</p>
+            <ul className="text-sm list-disc list-inside">
+                {fieldErrors.map(err => (
+                    <li key={err.field}><code>{err.field}</code>: {err.message}</li>
+                ))}
+            </ul>
+        </div>
+      )}
+
+      {lastError && lastError.code !== 'ModelMissing' && (
+        <div className="bg-red-100 border-l-4 border-red-500 text-red-700 p-4 mb-4 flex justify-between items-start" role="alert">
+            <div>
+                <p className="font-bold">Processing failed ({lastError.code})</p>
+                <p className="text-sm">{lastError.message}</p>
+            </div>
+            <button onClick={() => setLastError(null)} className="text-sm underline">Dismiss</button>
         </div>
       )}
 
@@ -289,17 +671,17 @@ export default function Settings() {
         <div className="mb-4">
             <label className="block text-sm font-medium text-gray-700 mb-1">Model Preset</label>
              <select
-                value={PRESETS.find(p => p.path === config.model_path)?.path || "custom"}
+                value={catalog.find(m => m.model_path === config.model_path)?.id || "custom"}
                 onChange={(e) => {
                     const val = e.target.value;
                     if (val !== "custom") {
-                        const preset = PRESETS.find(p => p.path === val);
-                        if (preset) {
+                        const entry = catalog.find(m => m.id === val);
+                        if (entry) {
                              const newConfig = {
                                  ...config,
-                                 model_path: preset.path,
-                                 tags_path: TAGS_PATH,
-                                 preprocessing: preset.preprocessing
+                                 model_path: entry.model_path,
+                                 tags_path: entry.tags_path,
+                                 preprocessing: entry.preprocessing
                              };
                              saveConfig(newConfig);
                         }
@@ -307,15 +689,21 @@ export default function Settings() {
                 }}
                 className="w-full p-2 border rounded bg-white mb-2 cursor-pointer"
              >
-                 {PRESETS.map(p => (
-                     <option key={p.path} value={p.path}>{p.name}</option>
+                 {catalog.map(m => (
+                     <option key={m.id} value={m.id}>
+                         {m.name}{m.size ? ` (${(m.size / 1048576).toFixed(0)} MB)` : ""}
+                     </option>
                  ))}
                  <option value="custom">Custom</option>
              </select>
 
-             {modelStatus === 'missing' && PRESETS.some(p => p.path === config.model_path) && (
+             {modelStatus === 'missing' && catalog.some(m => m.model_path === config.model_path) && (
                  <div className="mt-2 p-2 bg-yellow-50 text-yellow-800 border border-yellow-200 rounded flex items-center justify-between">
-                     <span className="text-sm">Model file not found locally.</span>
+                     <span className="text-sm">
+                         {lastError?.code === 'ModelMissing'
+                             ? "Tagging failed because the model file is missing."
+                             : "Model file not found locally."}
+                     </span>
                      <button
                         onClick={downloadCurrentModel}
                         disabled={!!downloadProgress}
@@ -325,6 +713,125 @@ export default function Settings() {
                      </button>
                  </div>
              )}
+
+             <div className="mt-2">
+                 <button
+                    onClick={verifyModels}
+                    disabled={verifying || !!downloadProgress}
+                    className="bg-gray-200 px-3 py-1 rounded text-sm hover:bg-gray-300 disabled:opacity-50"
+                 >
+                     {verifying ? "Verifying..." : "Verify Models"}
+                 </button>
+                 {modelChecks && (
+                     <ul className="mt-2 text-xs space-y-1">
+                         {modelChecks.map(check => (
+                             <li key={check.path} className={check.status === 'mismatch' || check.status === 'missing' ? 'text-red-600' : 'text-gray-700'}>
+                                 <span className="font-semibold uppercase mr-2">{check.status}</span>
+                                 <span className="break-all">{check.path}</span>
+                                 {check.status === 'mismatch' && (
+                                     <span className="block">expected {check.expected}, got {check.actual}</span>
+                                 )}
+                             </li>
+                         ))}
+                     </ul>
+                 )}
+             </div>
+        </div>
+
+        <div className="mb-4">
+            <div className="flex items-center justify-between mb-1">
+                <label className="block text-sm font-medium text-gray-700">Installed Models</label>
+                <button
+                    onClick={listInstalledModels}
+                    disabled={listingModels}
+                    className="bg-gray-200 px-3 py-1 rounded text-sm hover:bg-gray-300 disabled:opacity-50"
+                >
+                    {listingModels ? "Scanning..." : installed ? "Refresh" : "Show"}
+                </button>
+            </div>
+            {installed && (
+                <>
+                    <ul className="text-xs space-y-2">
+                        {installed.models.map(m => (
+                            <li key={m.path} className="flex justify-between items-start gap-2 border-b pb-1">
+                                <div className="min-w-0">
+                                    <span className="block break-all font-mono">{m.path}</span>
+                                    <span className="block text-gray-500">
+                                        {formatMiB(m.size)}
+                                        {" · "}{m.checksum.status}
+                                        {" · "}{m.tag_count != null ? `${m.tag_count} tags` : "no tags file"}
+                                        {m.input_shape && ` · input [${m.input_shape.join(", ")}]`}
+                                        {" · "}{m.last_used ? `last used ${new Date(m.last_used * 1000).toLocaleString()}` : "never used"}
+                                    </span>
+                                </div>
+                                <button
+                                    onClick={() => deleteModel(m.path)}
+                                    className="text-red-600 underline shrink-0"
+                                >
+                                    Delete
+                                </button>
+                            </li>
+                        ))}
+                    </ul>
+                    <p className="text-xs text-gray-500 mt-1">Disk usage: {formatMiB(installed.disk_usage)}</p>
+                </>
+            )}
+        </div>
+
+        <div className="mb-4">
+            <label className="block text-sm font-medium text-gray-700 mb-1">Download from Hugging Face</label>
+            <div className="flex gap-2">
+                <input
+                    type="text"
+                    value={hubRepo}
+                    onChange={(e) => setHubRepo(e.target.value)}
+                    placeholder="SmilingWolf/wd-eva02-large-tagger-v3"
+                    className="flex-1 p-2 border rounded text-sm"
+                />
+                <input
+                    type="text"
+                    value={hubRevision}
+                    onChange={(e) => setHubRevision(e.target.value)}
+                    placeholder="main"
+                    className="w-24 p-2 border rounded text-sm"
+                />
+                <button
+                    onClick={downloadHubModel}
+                    disabled={!hubRepo.trim() || !!downloadProgress}
+                    className="bg-blue-600 text-white px-3 py-2 rounded text-sm hover:bg-blue-700 disabled:opacity-50"
+                >
+                    Download
+                </button>
+            </div>
+            <p className="text-xs text-gray-500 mt-1">
+                Fetches model.onnx and selected_tags.csv from the repository and switches to the new model.
+            </p>
+            <label className="block text-xs text-gray-500 mt-2 mb-1">Hub Base URL</label>
+            <input
+                type="text"
+                value={config.model_hub.base_url}
+                onChange={(e) => setConfig({ ...config, model_hub: { base_url: e.target.value } })}
+                onBlur={() => saveConfig(config)}
+                className="w-full p-2 border rounded text-sm"
+            />
+        </div>
+
+        <div className="mb-4">
+            <label className="block text-sm font-medium text-gray-700 mb-1">Import Offline Model</label>
+            <div className="flex gap-2">
+                <button
+                    onClick={() => importModel(false)}
+                    className="bg-gray-200 text-gray-800 px-3 py-2 rounded hover:bg-gray-300 text-sm"
+                >
+                    Model + Tags...
+                </button>
+                <button
+                    onClick={() => importModel(true)}
+                    className="bg-gray-200 text-gray-800 px-3 py-2 rounded hover:bg-gray-300 text-sm"
+                >
+                    Bundle (.zip / .tar.gz)...
+                </button>
+            </div>
         </div>
 
         <div className="mb-4">
@@ -396,7 +903,7 @@ export default function Settings() {
                         <label className="block text-sm font-medium text-gray-700 mb-1">Color Format</label>
                         <select
                             value={config.preprocessing.format}
-                            onChange={(e) => updatePreprocessing('format', e.target.value)}
+                            onChange={(e) => updatePreprocessing('format', e.target.value as PreprocessConfig['format'])}
                             className="w-full p-2 border rounded bg-gray-50 text-sm"
                         >
                             <option value="bgr">BGR (Standard for WD14)</option>
@@ -422,6 +929,87 @@ export default function Settings() {
             </details>
         </div>
 
+      {/* Profiles */}
+      <div className="bg-white p-4 rounded shadow mb-6">
+        <h2 className="text-lg font-semibold mb-4 border-b pb-2">Profiles</h2>
+        <div className="flex gap-2 items-center">
+            <select
+                value={config.active_profile ?? ""}
+                onChange={(e) => e.target.value ? switchProfile(e.target.value) : updateField('active_profile', null)}
+                className="flex-1 p-2 border rounded bg-gray-50 text-sm"
+            >
+                <option value="">(no profile)</option>
+                {profiles.map(name => (
+                    <option key={name} value={name}>{name}</option>
+                ))}
+            </select>
+            <button
+                onClick={saveProfile}
+                className="bg-blue-600 text-white px-3 py-2 rounded hover:bg-blue-700 text-sm"
+            >
+                Save As...
+            </button>
+            {config.active_profile && (
+                <button
+                    onClick={() => deleteProfile(config.active_profile!)}
+                    className="bg-red-600 text-white px-3 py-2 rounded hover:bg-red-700 text-sm"
+                >
+                    Delete
+                </button>
+            )}
+        </div>
+        <p className="text-xs text-gray-500 mt-2">
+            A profile stores the threshold, formatting, exclusion list and model. Changes made while a profile is active are saved to it.
+            Profiles can also be switched from the tray menu, or used for a single run with <code>--profile NAME</code>.
+        </p>
+      </div>
+
+      {/* Share Settings */}
+      <div className="bg-white p-4 rounded shadow mb-6">
+        <h2 className="text-lg font-semibold mb-4 border-b pb-2">Share Settings</h2>
+        <div className="flex flex-wrap gap-4 mb-3">
+            {CONFIG_SECTIONS.map(({ id, label }) => (
+                <label key={id} className="flex items-center space-x-2 cursor-pointer text-sm">
+                    <input
+                        type="checkbox"
+                        checked={shareSections.includes(id)}
+                        onChange={(e) => setShareSections(e.target.checked
+                            ? [...shareSections, id]
+                            : shareSections.filter(s => s !== id))}
+                        className="w-4 h-4 text-blue-600 rounded focus:ring-blue-500"
+                    />
+                    <span>{label}</span>
+                </label>
+            ))}
+        </div>
+        <div className="flex gap-2 items-center">
+            <button
+                onClick={exportSettings}
+                className="bg-blue-600 text-white px-3 py-2 rounded hover:bg-blue-700 text-sm"
+            >
+                Export...
+            </button>
+            <button
+                onClick={importSettings}
+                className="bg-gray-200 text-gray-800 px-3 py-2 rounded hover:bg-gray-300 text-sm"
+            >
+                Import...
+            </button>
+            <label className="flex items-center space-x-2 cursor-pointer text-sm ml-2">
+                <input
+                    type="checkbox"
+                    checked={mergeImport}
+                    onChange={(e) => setMergeImport(e.target.checked)}
+                    className="w-4 h-4 text-blue-600 rounded focus:ring-blue-500"
+                />
+                <span>Merge (add to lists instead of replacing them)</span>
+            </label>
+        </div>
+        <p className="text-xs text-gray-500 mt-2">
+            With no section selected, all settings except the API server, watch folders and proxy credentials are exported, and the exclusion list, formatting and model settings are imported. Network settings are only imported when selected. Imported settings are checked like any other change before they are saved.
+        </p>
+      </div>
+
       {/* Threshold & Formatting */}
       <div className="bg-white p-4 rounded shadow mb-6">
         <h2 className="text-lg font-semibold mb-4 border-b pb-2">Processing</h2>
@@ -468,6 +1056,277 @@ export default function Settings() {
                 placeholder="bad_hands, lowres, ..."
             />
         </div>
+
+        <div className="mt-4">
+            <h3 className="text-sm font-medium text-gray-700 mb-2">Exclusion File</h3>
+            <div className="flex gap-2">
+                <input
+                    type="text"
+                    value={config.exclusion_file ?? ""}
+                    onChange={(e) => setConfig({ ...config, exclusion_file: e.target.value })}
+                    onBlur={() => saveConfig({ ...config, exclusion_file: config.exclusion_file?.trim() || null })}
+                    placeholder="/path/to/shared-exclusions.txt"
+                    className="flex-1 p-2 border rounded bg-gray-50 text-sm font-mono"
+                />
+                <button
+                    onClick={async () => {
+                        const selected = await open({
+                            multiple: false,
+                            filters: [{ name: 'Text Files', extensions: ['txt'] }],
+                        });
+                        if (typeof selected === 'string') updateField('exclusion_file', selected);
+                    }}
+                    className="bg-gray-200 text-gray-800 px-3 py-2 rounded hover:bg-gray-300 text-sm"
+                >
+                    Browse...
+                </button>
+            </div>
+            <p className="text-xs text-gray-500 mt-1">
+                Tags in this plain text file (one per line, <code>#</code> for comments) are excluded as well. It is read on every request, so a shared file stays up to date.
+            </p>
+        </div>
+      </div>
+
+      {/* Watch Folders */}
+      <div className="bg-white p-4 rounded shadow mb-6">
+        <h2 className="text-lg font-semibold mb-4 border-b pb-2">Watch Folders</h2>
+
+        <div className="mb-4">
+             <label className="flex items-center space-x-2 cursor-pointer">
+                <input
+                    type="checkbox"
+                    checked={config.watch_enabled}
+                    onChange={(e) => updateField('watch_enabled', e.target.checked)}
+                    className="w-4 h-4 text-blue-600 rounded focus:ring-blue-500"
+                />
+                <span className="text-sm font-medium">Automatically tag new images in these folders</span>
+            </label>
+        </div>
+
+        <ul className="mb-2 space-y-1">
+            {config.watch_folders.map(folder => (
+                <li key={folder} className="flex items-center gap-2">
+                    <span className="flex-1 p-2 border rounded bg-gray-50 text-sm font-mono truncate">{folder}</span>
+                    <button
+                        onClick={() => updateField('watch_folders', config.watch_folders.filter(f => f !== folder))}
+                        className="bg-red-600 text-white px-3 py-2 rounded hover:bg-red-700 text-sm"
+                    >
+                        Remove
+                    </button>
+                </li>
+            ))}
+        </ul>
+        <button
+            onClick={async () => {
+                const selected = await open({ directory: true });
+                if (selected && typeof selected === 'string' && !config.watch_folders.includes(selected)) {
+                    updateField('watch_folders', [...config.watch_folders, selected]);
+                }
+            }}
+            className="bg-gray-200 text-gray-800 px-3 py-2 rounded hover:bg-gray-300 text-sm"
+        >
+            Add Folder
+        </button>
+        <p className="text-xs text-gray-500 mt-2">
+            Results are appended to <code>omni-tagger-results.jsonl</code> inside each folder instead of the clipboard.
+        </p>
+      </div>
+
+      {/* Local HTTP API */}
+      <div className="bg-white p-4 rounded shadow mb-6">
+        <h2 className="text-lg font-semibold mb-4 border-b pb-2">Local HTTP API</h2>
+
+        <div className="mb-4">
+             <label className="flex items-center space-x-2 cursor-pointer">
+                <input
+                    type="checkbox"
+                    checked={config.api_server.enabled}
+                    onChange={(e) => updateApiServer('enabled', e.target.checked)}
+                    className="w-4 h-4 text-blue-600 rounded focus:ring-blue-500"
+                />
+                <span className="text-sm font-medium">Serve <code>/tag</code>, <code>/models</code> and <code>/health</code> on localhost</span>
+            </label>
+        </div>
+
+        <div className="grid grid-cols-1 md:grid-cols-3 gap-4">
+            <div>
+                <label className="block text-sm font-medium text-gray-700 mb-1">Port</label>
+                <input
+                    type="number"
+                    value={config.api_server.port}
+                    onChange={(e) => updateApiServer('port', parseInt(e.target.value) || 0)}
+                    className="w-full p-2 border rounded bg-gray-50 text-sm"
+                />
+            </div>
+            <div className="md:col-span-2">
+                <label className="block text-sm font-medium text-gray-700 mb-1">Bearer Token</label>
+                <input
+                    type="text"
+                    value={config.api_server.token}
+                    onChange={(e) => updateApiServer('token', e.target.value)}
+                    placeholder="Required while the server is enabled"
+                    className="w-full p-2 border rounded bg-gray-50 text-sm font-mono"
+                />
+                <button
+                    onClick={() => updateApiServer('token', generateToken())}
+                    className="mt-1 text-xs text-blue-600 hover:underline"
+                >
+                    Generate new token
+                </button>
+            </div>
+        </div>
+        <p className="text-xs text-gray-500 mt-2">
+            The server only listens on 127.0.0.1, requires the token on every request and refuses requests addressed to other host names. Changes take effect after restarting OmniTagger.
+        </p>
+      </div>
+
+      {/* Network */}
+      <div className="bg-white p-4 rounded shadow mb-6">
+        <h2 className="text-lg font-semibold mb-4 border-b pb-2">Network</h2>
+
+        <div className="mb-4">
+            <label className="block text-sm font-medium text-gray-700 mb-1">Proxy</label>
+            <input
+                type="text"
+                value={config.network.proxy}
+                onChange={(e) => setConfig({ ...config, network: { ...config.network, proxy: e.target.value } })}
+                onBlur={() => saveConfig(config)}
+                placeholder="http://proxy:3128 or socks5h://proxy:1080"
+                className="w-full p-2 border rounded bg-gray-50 text-sm font-mono"
+            />
+            <p className="text-xs text-gray-500 mt-1">
+                Leave empty to use the <code>HTTPS_PROXY</code> / <code>HTTP_PROXY</code> environment variables.
+            </p>
+        </div>
+
+        <div className="mb-4">
+            <label className="block text-sm font-medium text-gray-700 mb-1">No Proxy</label>
+            <input
+                type="text"
+                value={noProxyText}
+                onChange={(e) => setNoProxyText(e.target.value)}
+                onBlur={() => updateNetwork('no_proxy', splitList(noProxyText))}
+                placeholder="localhost, .corp.example, 10.0.0.0/8"
+                className="w-full p-2 border rounded bg-gray-50 text-sm font-mono"
+            />
+        </div>
+
+        <div>
+            <label className="block text-sm font-medium text-gray-700 mb-1">Extra CA Bundle</label>
+            <div className="flex gap-2">
+                <input
+                    type="text"
+                    value={config.network.ca_bundle}
+                    onChange={(e) => setConfig({ ...config, network: { ...config.network, ca_bundle: e.target.value } })}
+                    onBlur={() => saveConfig(config)}
+                    placeholder="/path/to/corporate-ca.pem"
+                    className="flex-1 p-2 border rounded bg-gray-50 text-sm font-mono"
+                />
+                <button
+                    onClick={async () => {
+                        const selected = await open({
+                            multiple: false,
+                            filters: [{ name: 'PEM Certificates', extensions: ['pem', 'crt'] }],
+                        });
+                        if (typeof selected === 'string') updateNetwork('ca_bundle', selected);
+                    }}
+                    className="bg-gray-200 text-gray-800 px-3 py-2 rounded hover:bg-gray-300 text-sm"
+                >
+                    Browse...
+                </button>
+            </div>
+            <p className="text-xs text-gray-500 mt-1">
+                Certificates in this PEM file are trusted in addition to the system ones. Applies to model downloads and image URLs.
+            </p>
+        </div>
+      </div>
+
+      {/* Image URLs */}
+      <div className="bg-white p-4 rounded shadow mb-6">
+        <h2 className="text-lg font-semibold mb-4 border-b pb-2">Image URLs</h2>
+
+        <div className="grid grid-cols-1 md:grid-cols-2 gap-4 mb-4">
+            <div>
+                <label className="block text-sm font-medium text-gray-700 mb-1">Allowed Domains</label>
+                <input
+                    type="text"
+                    value={allowDomainsText}
+                    onChange={(e) => setAllowDomainsText(e.target.value)}
+                    onBlur={() => updateUrlPolicy('allow_domains', splitList(allowDomainsText))}
+                    placeholder="Any domain"
+                    className="w-full p-2 border rounded bg-gray-50 text-sm font-mono"
+                />
+            </div>
+            <div>
+                <label className="block text-sm font-medium text-gray-700 mb-1">Blocked Domains</label>
+                <input
+                    type="text"
+                    value={denyDomainsText}
+                    onChange={(e) => setDenyDomainsText(e.target.value)}
+                    onBlur={() => updateUrlPolicy('deny_domains', splitList(denyDomainsText))}
+                    placeholder="ads.example.com, ..."
+                    className="w-full p-2 border rounded bg-gray-50 text-sm font-mono"
+                />
+            </div>
+        </div>
+
+        <div className="mb-4">
+             <label className="flex items-center space-x-2 cursor-pointer">
+                <input
+                    type="checkbox"
+                    checked={config.url_policy.allow_private_networks}
+                    onChange={(e) => updateUrlPolicy('allow_private_networks', e.target.checked)}
+                    className="w-4 h-4 text-blue-600 rounded focus:ring-blue-500"
+                />
+                <span className="text-sm font-medium">Allow private networks (e.g. a NAS on the LAN)</span>
+            </label>
+        </div>
+
+        <div className="grid grid-cols-1 md:grid-cols-2 gap-4">
+            <div>
+                <label className="block text-sm font-medium text-gray-700 mb-1">Max Size (MB)</label>
+                <input
+                    type="number"
+                    min="1"
+                    value={Math.round(config.url_policy.max_size / (1024 * 1024))}
+                    onChange={(e) => updateUrlPolicy('max_size', (parseInt(e.target.value) || 1) * 1024 * 1024)}
+                    className="w-full p-2 border rounded bg-gray-50 text-sm"
+                />
+            </div>
+            <div>
+                <label className="block text-sm font-medium text-gray-700 mb-1">Total Timeout incl. Retries (seconds)</label>
+                <input
+                    type="number"
+                    min="1"
+                    value={config.url_policy.timeout_secs}
+                    onChange={(e) => updateUrlPolicy('timeout_secs', parseInt(e.target.value) || 1)}
+                    className="w-full p-2 border rounded bg-gray-50 text-sm"
+                />
+            </div>
+            <div>
+                <label className="block text-sm font-medium text-gray-700 mb-1">Connect Timeout (seconds)</label>
+                <input
+                    type="number"
+                    min="1"
+                    value={config.url_policy.connect_timeout_secs}
+                    onChange={(e) => updateUrlPolicy('connect_timeout_secs', parseInt(e.target.value) || 1)}
+                    className="w-full p-2 border rounded bg-gray-50 text-sm"
+                />
+            </div>
+            <div>
+                <label className="block text-sm font-medium text-gray-700 mb-1">Retries</label>
+                <input
+                    type="number"
+                    min="0"
+                    value={config.url_policy.retries}
+                    onChange={(e) => updateUrlPolicy('retries', parseInt(e.target.value) || 0)}
+                    className="w-full p-2 border rounded bg-gray-50 text-sm"
+                />
+            </div>
+        </div>
+        <p className="text-xs text-gray-500 mt-2">
+            Domains match their subdomains too. Loopback and link-local addresses are always blocked.
+        </p>
       </div>
     </div>
   );